@@ -0,0 +1,183 @@
+//! Reader for the `SharePriceFeed` account published by the reza-vault
+//! program, for programs that should not depend on the `reza_vault` crate.
+//!
+//! ```ignore
+//! // the caller checks the owner and which vault the feed belongs to
+//! require_keys_eq!(*feed_info.owner, reza_vault_id);
+//! let feed = SharePriceFeed::try_from_bytes(&feed_info.try_borrow_data()?)?;
+//! require!(feed.vault == expected_vault.to_bytes());
+//! let assets = feed.assets_for_shares(shares, Clock::get()?.slot)?;
+//! ```
+//!
+//! The feed lives at the PDA `["share_price_feed", vault]` of the reza-vault
+//! program.
+
+/// reza-vault program id (`Ht6zRm9hg3ebBpGeYGrgosdq7qQVNa6qQsmt3S7gdrv6`).
+pub const REZA_VAULT_PROGRAM_ID: [u8; 32] = [
+    250, 210, 98, 152, 34, 207, 131, 249, 155, 84, 46, 214, 147, 130, 233, 120, 29, 201, 179, 75,
+    15, 146, 169, 3, 228, 243, 28, 197, 243, 203, 248, 251,
+];
+
+/// Anchor account discriminator, `sha256("account:SharePriceFeed")[..8]`.
+pub const DISCRIMINATOR: [u8; 8] = [250, 142, 226, 57, 82, 82, 185, 95];
+
+/// Bytes read by `try_from_bytes`; later versions may only append.
+pub const FEED_LEN: usize = 133;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedError {
+    /// Account data is shorter than `FEED_LEN`.
+    InvalidLength,
+    /// Account data is not a `SharePriceFeed`.
+    InvalidDiscriminator,
+    /// Last update is more than `max_staleness_slots` behind.
+    Stale,
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharePriceFeed {
+    pub vault: [u8; 32],
+    pub share_mint: [u8; 32],
+    /// Assets per share, scaled by `10^-expo`.
+    pub price: u64,
+    pub expo: i32,
+    /// Absolute price move of the last update.
+    pub last_price_move: u64,
+    pub total_asset: u64,
+    pub total_shares: u64,
+    pub last_update_slot: u64,
+    pub last_update_ts: i64,
+    /// Slots after `last_update_slot` during which `price` is served; the
+    /// program keeps it at 1 or more.
+    pub max_staleness_slots: u64,
+}
+
+impl SharePriceFeed {
+    pub fn try_from_bytes(data: &[u8]) -> Result<Self, FeedError> {
+        if data.len() < FEED_LEN {
+            return Err(FeedError::InvalidLength);
+        }
+        if data[..8] != DISCRIMINATOR {
+            return Err(FeedError::InvalidDiscriminator);
+        }
+        Ok(Self {
+            vault: bytes(data, 8),
+            share_mint: bytes(data, 40),
+            price: u64::from_le_bytes(bytes(data, 72)),
+            expo: i32::from_le_bytes(bytes(data, 80)),
+            last_price_move: u64::from_le_bytes(bytes(data, 84)),
+            total_asset: u64::from_le_bytes(bytes(data, 92)),
+            total_shares: u64::from_le_bytes(bytes(data, 100)),
+            last_update_slot: u64::from_le_bytes(bytes(data, 108)),
+            last_update_ts: i64::from_le_bytes(bytes(data, 116)),
+            max_staleness_slots: u64::from_le_bytes(bytes(data, 124)),
+        })
+    }
+
+    /// `price`, or `Stale` once `current_slot` is past the feed's staleness
+    /// limit.
+    pub fn price_at(&self, current_slot: u64) -> Result<u64, FeedError> {
+        if current_slot.saturating_sub(self.last_update_slot) > self.max_staleness_slots {
+            return Err(FeedError::Stale);
+        }
+        Ok(self.price)
+    }
+
+    /// Assets `shares` are worth at the feed price, rounded down.
+    pub fn assets_for_shares(&self, shares: u64, current_slot: u64) -> Result<u64, FeedError> {
+        let price = self.price_at(current_slot)?;
+        let scale = 10u128
+            .checked_pow(self.expo.unsigned_abs())
+            .ok_or(FeedError::Overflow)?;
+        let assets = if self.expo < 0 {
+            shares as u128 * price as u128 / scale
+        } else {
+            (shares as u128 * price as u128)
+                .checked_mul(scale)
+                .ok_or(FeedError::Overflow)?
+        };
+        u64::try_from(assets).map_err(|_| FeedError::Overflow)
+    }
+}
+
+fn bytes<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+    data[offset..offset + N].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::prelude::Pubkey;
+    use anchor_lang::{AccountSerialize, Discriminator};
+
+    fn vault_feed() -> reza_vault::SharePriceFeed {
+        reza_vault::SharePriceFeed {
+            vault: Pubkey::new_unique(),
+            share_mint: Pubkey::new_unique(),
+            price: 1_050_000_000,
+            expo: reza_vault::SharePriceFeed::EXPO,
+            last_price_move: 50_000_000,
+            total_asset: 105_000_000,
+            total_shares: 100_000_000,
+            last_update_slot: 1_000,
+            last_update_ts: 1_700_000_000,
+            max_staleness_slots: 150,
+            bump: 254,
+        }
+    }
+
+    fn serialize(feed: &reza_vault::SharePriceFeed) -> Vec<u8> {
+        let mut data = Vec::new();
+        feed.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn constants_match_the_program() {
+        assert_eq!(REZA_VAULT_PROGRAM_ID, reza_vault::ID.to_bytes());
+        assert_eq!(DISCRIMINATOR, reza_vault::SharePriceFeed::DISCRIMINATOR);
+        assert_eq!(serialize(&vault_feed()).len(), FEED_LEN);
+    }
+
+    #[test]
+    fn reads_what_the_program_writes() {
+        let source = vault_feed();
+        let feed = SharePriceFeed::try_from_bytes(&serialize(&source)).unwrap();
+        assert_eq!(feed.vault, source.vault.to_bytes());
+        assert_eq!(feed.share_mint, source.share_mint.to_bytes());
+        assert_eq!(feed.price, source.price);
+        assert_eq!(feed.expo, source.expo);
+        assert_eq!(feed.last_price_move, source.last_price_move);
+        assert_eq!(feed.total_asset, source.total_asset);
+        assert_eq!(feed.total_shares, source.total_shares);
+        assert_eq!(feed.last_update_slot, source.last_update_slot);
+        assert_eq!(feed.last_update_ts, source.last_update_ts);
+        assert_eq!(feed.max_staleness_slots, source.max_staleness_slots);
+    }
+
+    #[test]
+    fn rejects_short_or_foreign_data() {
+        let data = serialize(&vault_feed());
+        assert_eq!(
+            SharePriceFeed::try_from_bytes(&data[..FEED_LEN - 1]),
+            Err(FeedError::InvalidLength)
+        );
+        let mut foreign = data.clone();
+        foreign[0] ^= 1;
+        assert_eq!(
+            SharePriceFeed::try_from_bytes(&foreign),
+            Err(FeedError::InvalidDiscriminator)
+        );
+    }
+
+    #[test]
+    fn prices_shares_until_stale() {
+        let feed = SharePriceFeed::try_from_bytes(&serialize(&vault_feed())).unwrap();
+        assert_eq!(feed.assets_for_shares(2_000_000, 1_150), Ok(2_100_000));
+        assert_eq!(
+            feed.assets_for_shares(2_000_000, 1_151),
+            Err(FeedError::Stale)
+        );
+    }
+}
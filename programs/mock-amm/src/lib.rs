@@ -0,0 +1,168 @@
+// anchor 0.31's generated IDL handlers still call the deprecated AccountInfo::realloc
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+declare_id!("Fh7L4oWfeZwSGRcg5TT7BovW5BvDtDNJxpfAdAgsm4AR");
+
+/// Test-only AMM: swaps `mint_in` for `mint_out` at a fixed `rate_num / rate_den`.
+///
+/// `swap` follows the account layout reza-vault's `compound` expects from a
+/// swap program: `[authority, source, destination, token_program, ...]`.
+#[program]
+pub mod mock_amm {
+    use super::*;
+
+    // ─────────────────────────────────────────
+    // INITIALISE ─ one pool per (mint_in, mint_out)
+    // ─────────────────────────────────────────
+    pub fn initialize_pool(
+        ctx: Context<InitializePool>,
+        rate_num: u64,
+        rate_den: u64,
+    ) -> Result<()> {
+        require!(rate_num > 0 && rate_den > 0, AmmError::InvalidRate);
+        let pool = &mut ctx.accounts.pool;
+        pool.mint_in = ctx.accounts.mint_in.key();
+        pool.mint_out = ctx.accounts.mint_out.key();
+        pool.vault_in = ctx.accounts.vault_in.key();
+        pool.vault_out = ctx.accounts.vault_out.key();
+        pool.rate_num = rate_num;
+        pool.rate_den = rate_den;
+        pool.bump = ctx.bumps.pool;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // SWAP ─ amount_in of mint_in ➜ fixed-rate mint_out
+    // ─────────────────────────────────────────
+    pub fn swap(ctx: Context<Swap>, amount_in: u64, min_amount_out: u64) -> Result<()> {
+        require!(amount_in > 0, AmmError::InvalidAmount);
+        let pool = &ctx.accounts.pool;
+
+        let amount_out = (amount_in as u128)
+            .checked_mul(pool.rate_num as u128)
+            .ok_or(AmmError::Overflow)?
+            / pool.rate_den as u128;
+        let amount_out = u64::try_from(amount_out).map_err(|_| AmmError::Overflow)?;
+        require!(amount_out >= min_amount_out, AmmError::SlippageExceeded);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.source.to_account_info(),
+                    to: ctx.accounts.vault_in.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            ),
+            amount_in,
+        )?;
+
+        let mint_in = pool.mint_in;
+        let mint_out = pool.mint_out;
+        let pool_seeds: &[&[u8]] = &[b"pool", mint_in.as_ref(), mint_out.as_ref(), &[pool.bump]];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault_out.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                &[pool_seeds],
+            ),
+            amount_out,
+        )?;
+        Ok(())
+    }
+}
+
+#[account]
+pub struct Pool {
+    pub mint_in: Pubkey,
+    pub mint_out: Pubkey,
+    pub vault_in: Pubkey,
+    pub vault_out: Pubkey,
+    pub rate_num: u64,
+    pub rate_den: u64,
+    pub bump: u8,
+}
+const _POOL_SIZE: usize = 8 + 32 * 4 + 8 * 2 + 1;
+
+#[error_code]
+pub enum AmmError {
+    #[msg("Amount must be greater than zero.")]
+    InvalidAmount,
+    #[msg("Rate numerator and denominator must be greater than zero.")]
+    InvalidRate,
+    #[msg("Output is below the requested minimum.")]
+    SlippageExceeded,
+    #[msg("Math overflow.")]
+    Overflow,
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool", mint_in.key().as_ref(), mint_out.key().as_ref()],
+        bump,
+        space = _POOL_SIZE
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool_vault", pool.key().as_ref(), mint_in.key().as_ref()],
+        bump,
+        token::mint = mint_in,
+        token::authority = pool
+    )]
+    pub vault_in: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool_vault", pool.key().as_ref(), mint_out.key().as_ref()],
+        bump,
+        token::mint = mint_out,
+        token::authority = pool
+    )]
+    pub vault_out: Box<Account<'info, TokenAccount>>,
+
+    pub mint_in: Box<Account<'info, Mint>>,
+    pub mint_out: Box<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut, token::mint = pool.mint_in)]
+    pub source: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = pool.mint_out)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    #[account(has_one = vault_in, has_one = vault_out)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub vault_in: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub vault_out: Account<'info, TokenAccount>,
+}
@@ -0,0 +1,94 @@
+// anchor 0.31's generated IDL handlers still call the deprecated AccountInfo::realloc
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+
+declare_id!("S6b6EcM41ajLfBThf58JCTnoPwM58JdpfE1cZBLxtzr");
+
+/// Test-only price oracle: writes accounts in the layout of Pyth's
+/// `PriceUpdateV2` (same name, so same discriminator), which reza-vault
+/// reads for its USD NAV and deposit cap.
+#[program]
+pub mod mock_oracle {
+    use super::*;
+
+    // ─────────────────────────────────────────
+    // INITIALISE ─ one price account per keypair
+    // ─────────────────────────────────────────
+    pub fn initialize_price(ctx: Context<InitializePrice>, feed_id: [u8; 32]) -> Result<()> {
+        let update = &mut ctx.accounts.price_update;
+        update.write_authority = ctx.accounts.authority.key();
+        update.verification_level = VerificationLevel::Full;
+        update.price_message.feed_id = feed_id;
+        update.posted_slot = Clock::get()?.slot;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // SET PRICE ─ price * 10^exponent, published at `publish_time`
+    // ─────────────────────────────────────────
+    pub fn set_price(
+        ctx: Context<SetPrice>,
+        price: i64,
+        conf: u64,
+        exponent: i32,
+        publish_time: i64,
+    ) -> Result<()> {
+        let message = &mut ctx.accounts.price_update.price_message;
+        message.prev_publish_time = message.publish_time;
+        message.price = price;
+        message.conf = conf;
+        message.exponent = exponent;
+        message.publish_time = publish_time;
+        message.ema_price = price;
+        message.ema_conf = conf;
+        ctx.accounts.price_update.posted_slot = Clock::get()?.slot;
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationLevel {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceFeedMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+#[account]
+pub struct PriceUpdateV2 {
+    pub write_authority: Pubkey,
+    pub verification_level: VerificationLevel,
+    pub price_message: PriceFeedMessage,
+    pub posted_slot: u64,
+}
+const _PRICE_UPDATE_SIZE: usize = 8 + 32 + 2 + 32 + 8 * 2 + 4 + 8 * 4 + 8;
+
+#[derive(Accounts)]
+pub struct InitializePrice<'info> {
+    #[account(init, payer = authority, space = _PRICE_UPDATE_SIZE)]
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPrice<'info> {
+    #[account(mut, has_one = write_authority)]
+    pub price_update: Account<'info, PriceUpdateV2>,
+
+    pub write_authority: Signer<'info>,
+}
@@ -1,5 +1,28 @@
+// anchor 0.31's generated IDL handlers still call the deprecated AccountInfo::realloc
+#![allow(deprecated)]
+// initialize_vault's argument list is mirrored into the generated cpi module
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::solana_program::{
+    ed25519_program, instruction::Instruction, program::invoke_signed,
+};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+};
+use anchor_spl::token_2022::{self, spl_token_2022, Token2022};
+use anchor_spl::token_2022_extensions::{
+    self as token_extensions,
+    spl_pod::optional_keys::OptionalNonZeroPubkey,
+    spl_token_metadata_interface::state::{Field, TokenMetadata},
+};
+use anchor_spl::token_interface;
 
 declare_id!("Ht6zRm9hg3ebBpGeYGrgosdq7qQVNa6qQsmt3S7gdrv6");
 
@@ -13,9 +36,119 @@ pub mod reza_vault {
     pub fn initialize_vault(
         ctx: Context<InitializeVault>,
         _vault_bump: u8,
-        _auth_bump: u8,
+        auth_bump: u8,
+        vault_id: u64,
+        name: String,
+        symbol: String,
+        uri: String,
+        non_transferable: bool,
     ) -> Result<()> {
+        // ╭──────────────────────────────────╮
+        // │  Share mint: Token-2022 with     │
+        // │  on-mint metadata                │
+        // ╰──────────────────────────────────╯
+        let share_mint_key = ctx.accounts.share_mint.key();
+        let vault_authority_key = ctx.accounts.vault_authority.key();
+        let metadata = share_metadata(share_mint_key, vault_authority_key, name, symbol, uri)?;
+
+        let mut extensions = vec![ExtensionType::MetadataPointer];
+        if non_transferable {
+            // soulbound: shares can only be minted and burned by the vault
+            extensions.push(ExtensionType::NonTransferable);
+        }
+        let transfer_hook_program = ctx
+            .accounts
+            .transfer_hook_program
+            .as_ref()
+            .map(|program| program.key());
+        if transfer_hook_program.is_some() {
+            extensions.push(ExtensionType::TransferHook);
+        }
+        let mint_len =
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&extensions)?;
+        // fund the metadata TLV up front, token-2022 reallocs into it on initialise
+        let lamports = Rent::get()?.minimum_balance(mint_len + metadata.tlv_size_of()?);
+        system_program::create_account(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: ctx.accounts.admin.to_account_info(),
+                    to: ctx.accounts.share_mint.to_account_info(),
+                },
+            ),
+            lamports,
+            mint_len as u64,
+            &token_2022::ID,
+        )?;
+        token_extensions::metadata_pointer_initialize(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_extensions::MetadataPointerInitialize {
+                    token_program_id: ctx.accounts.share_token_program.to_account_info(),
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                },
+            ),
+            Some(vault_authority_key),
+            Some(share_mint_key),
+        )?;
+        if let Some(hook_program) = transfer_hook_program {
+            token_extensions::transfer_hook_initialize(
+                CpiContext::new(
+                    ctx.accounts.share_token_program.to_account_info(),
+                    token_extensions::TransferHookInitialize {
+                        token_program_id: ctx.accounts.share_token_program.to_account_info(),
+                        mint: ctx.accounts.share_mint.to_account_info(),
+                    },
+                ),
+                Some(vault_authority_key),
+                Some(hook_program),
+            )?;
+        }
+        if non_transferable {
+            token_extensions::non_transferable_mint_initialize(CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_extensions::NonTransferableMintInitialize {
+                    token_program_id: ctx.accounts.share_token_program.to_account_info(),
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                },
+            ))?;
+        }
+        token_2022::initialize_mint2(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_2022::InitializeMint2 {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                },
+            ),
+            ctx.accounts.asset_mint.decimals,
+            &vault_authority_key,
+            Some(&vault_authority_key),
+        )?;
+
+        let state_key = ctx.accounts.vault_state.key();
+        let vault_authority_seeds: &[&[u8]] =
+            &[b"vault_authority", state_key.as_ref(), &[auth_bump]];
+        token_extensions::token_metadata_initialize(
+            CpiContext::new_with_signer(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_extensions::TokenMetadataInitialize {
+                    program_id: ctx.accounts.share_token_program.to_account_info(),
+                    metadata: ctx.accounts.share_mint.to_account_info(),
+                    update_authority: ctx.accounts.vault_authority.to_account_info(),
+                    mint_authority: ctx.accounts.vault_authority.to_account_info(),
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                },
+                &[vault_authority_seeds],
+            ),
+            metadata.name,
+            metadata.symbol,
+            metadata.uri,
+        )?;
+
         let state = &mut ctx.accounts.vault_state;
+        state.vault_id = vault_id;
+        state.bump = ctx.bumps.vault_state;
+        state.protocol_fees_accrued = 0;
         state.asset_mint = ctx.accounts.asset_mint.key();
         state.share_mint = ctx.accounts.share_mint.key();
         state.vault_authority = ctx.accounts.vault_authority.key();
@@ -24,137 +157,615 @@ pub mod reza_vault {
         state.total_asset = 0;
         state.total_shares = 0;
         state.paused = false;
+        state.epoch_mode = false;
+        state.epoch_duration = 0;
+        state.current_epoch = 0;
+        state.epoch_started_at = 0;
+        state.pending_deposit_assets = 0;
+        state.pending_redeem_shares = 0;
+        state.lockup_period = 0;
+        state.early_exit_penalty_bps = 0;
+        state.gate_bps = 0;
+        state.gate_window = 0;
+        state.gate_max_hits = 0;
+        state.gate_window_start = 0;
+        state.gate_window_cap = 0;
+        state.gate_window_outflow = 0;
+        state.gate_window_hits = 0;
+        state.queued_redeem_shares = 0;
+        state.swap_program = Pubkey::default();
+        state.keeper_bounty_bps = 0;
+        state.non_transferable = non_transferable;
+        state.transfer_hook_program = transfer_hook_program.unwrap_or_default();
+        state.flash_fee_bps = 0;
+        state.flash_paused = false;
+        state.flash_loan_amount = 0;
+        state.oracle = Pubkey::default();
+        state.oracle_max_age = 0;
+        state.oracle_max_conf_bps = 0;
+        state.deposit_cap_usd = 0;
+        state.asset_decimals = ctx.accounts.asset_mint.decimals;
+        state.reward_pool_count = 0;
+        state.compound_max_slippage_bps = 0;
+        state.has_share_price_feed = false;
+        state.has_price_history = false;
+
+        // ╭──────────────────────────────────╮
+        // │  Register with the protocol      │
+        // ╰──────────────────────────────────╯
+        let config = &mut ctx.accounts.protocol_config;
+        require!(
+            config.allowed_asset_mints.contains(&state.asset_mint),
+            VaultError::AssetMintNotAllowed
+        );
+        let entry = &mut ctx.accounts.vault_registry;
+        entry.index = config.vault_count;
+        entry.vault = state.key();
+        entry.asset_mint = state.asset_mint;
+        entry.vault_id = vault_id;
+        entry.admin = state.admin;
+        config.vault_count = config
+            .vault_count
+            .checked_add(1)
+            .ok_or(VaultError::Overflow)?;
+
+        emit!(VaultRegisteredEvent {
+            index: entry.index,
+            vault: entry.vault,
+            asset_mint: entry.asset_mint,
+            vault_id,
+            admin: entry.admin,
+        });
         Ok(())
     }
 
     // ─────────────────────────────────────────
-    // DEPOSIT ASSET A  ➜ mint vault shares
+    // PROTOCOL: one-time creation of the global config
     // ─────────────────────────────────────────
-    pub fn deposit_asset_a(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        // ╭──────────────────────────────────╮
-        // │  Safety & vault-state sanity     │
-        // ╰──────────────────────────────────╯
-        require!(amount > 0, VaultError::InvalidAmount);
-        require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+    pub fn initialize_protocol(ctx: Context<InitializeProtocol>, fee_share_bps: u16) -> Result<()> {
+        require!(fee_share_bps as u64 <= MAX_BPS, VaultError::InvalidBps);
+        let config = &mut ctx.accounts.protocol_config;
+        config.owner = ctx.accounts.owner.key();
+        config.fee_share_bps = fee_share_bps;
+        config.allowed_asset_mints = Vec::new();
+        config.vault_count = 0;
+        config.bump = ctx.bumps.protocol_config;
+        config.guardian = ctx.accounts.owner.key();
+        config.global_pause = false;
+        Ok(())
+    }
 
-        let state = &mut ctx.accounts.vault_state;
+    // ─────────────────────────────────────────
+    // PROTOCOL: share of vault fee revenue taken by the protocol
+    // ─────────────────────────────────────────
+    pub fn set_protocol_fee_share(ctx: Context<ProtocolAdmin>, fee_share_bps: u16) -> Result<()> {
+        require!(fee_share_bps as u64 <= MAX_BPS, VaultError::InvalidBps);
+        ctx.accounts.protocol_config.fee_share_bps = fee_share_bps;
+        Ok(())
+    }
 
-        // ╭──────────────────────────────────╮
-        // │  Transfer Asset A into vault     │
-        // ╰──────────────────────────────────╯
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_asset_account.to_account_info(),
-            to: ctx.accounts.vault_asset_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        token::transfer(
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
-            amount,
-        )?;
+    // ─────────────────────────────────────────
+    // PROTOCOL: hand the kill switch to a guardian key
+    // ─────────────────────────────────────────
+    pub fn set_guardian(ctx: Context<ProtocolAdmin>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts.protocol_config.guardian = guardian;
+        Ok(())
+    }
 
-        // ╭──────────────────────────────────╮
-        // │  Calculate shares to mint        │
-        // ╰──────────────────────────────────╯
-        let shares_to_mint: u64 = if state.total_shares == 0 || state.total_asset == 0 {
-            // first deposit ⇒ 1:1 share ratio
-            amount
-        } else {
-            // shares = amount * total_shares / total_asset   (all in u128 for safety)
-            let shares = (amount as u128)
-                .checked_mul(state.total_shares as u128)
-                .unwrap()
-                .checked_div(state.total_asset as u128)
-                .unwrap();
-            shares as u64
-        };
-        require!(shares_to_mint > 0, VaultError::RoundingError);
+    // ─────────────────────────────────────────
+    // GUARDIAN: freeze / unfreeze every vault at once
+    // ─────────────────────────────────────────
+    pub fn set_global_pause(ctx: Context<GuardianAction>, pause: bool) -> Result<()> {
+        ctx.accounts.protocol_config.global_pause = pause;
+        emit!(GlobalPauseEvent {
+            paused: pause,
+            guardian: ctx.accounts.guardian.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
 
-        // ╭──────────────────────────────────╮
-        // │  Mint shares to the user         │
-        // ╰──────────────────────────────────╯
-        let state_key = state.key(); // own the Pubkey for the rest of the function
+    // ─────────────────────────────────────────
+    // PROTOCOL: asset-mint allow-list for new vaults
+    // ─────────────────────────────────────────
+    pub fn add_allowed_mint(ctx: Context<ProtocolAdmin>, mint: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        if !config.allowed_asset_mints.contains(&mint) {
+            require!(
+                config.allowed_asset_mints.len() < MAX_ALLOWED_MINTS,
+                VaultError::AllowListFull
+            );
+            config.allowed_asset_mints.push(mint);
+        }
+        Ok(())
+    }
+
+    pub fn remove_allowed_mint(ctx: Context<ProtocolAdmin>, mint: Pubkey) -> Result<()> {
+        // existing vaults keep working, only new registrations are blocked
+        ctx.accounts
+            .protocol_config
+            .allowed_asset_mints
+            .retain(|m| *m != mint);
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // PROTOCOL: sweep a vault's accrued protocol fees
+    // ─────────────────────────────────────────
+    pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+        let state = &mut ctx.accounts.vault_state;
+        let amount = state.protocol_fees_accrued;
+        require!(amount > 0, VaultError::NothingToClaim);
+
+        let state_key = state.key();
         let vault_authority_seeds: &[&[u8]] = &[
             b"vault_authority",
             state_key.as_ref(),
             &[ctx.bumps.vault_authority],
         ];
-        let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds]; // &[&[u8]] → &[&[&[u8]]]
+        let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds];
 
-        let cpi_accounts = MintTo {
-            mint: ctx.accounts.share_mint.to_account_info(),
-            to: ctx.accounts.user_share_account.to_account_info(),
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.vault_asset_account.to_account_info(),
+            to: ctx.accounts.destination.to_account_info(),
             authority: ctx.accounts.vault_authority.to_account_info(),
         };
-        token::mint_to(
+        token::transfer(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 cpi_accounts,
                 signer_seeds,
             ),
-            shares_to_mint,
+            amount,
         )?;
+        state.protocol_fees_accrued = 0;
+
+        emit!(ProtocolFeesCollectedEvent {
+            vault: state_key,
+            amount,
+            destination: ctx.accounts.destination.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // DEPOSIT ASSET A  ➜ mint vault shares
+    // ─────────────────────────────────────────
+    pub fn deposit_asset_a<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deposit<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        deposit_assets(ctx, amount, false).map(|_| ())
+    }
+
+    // ─────────────────────────────────────────
+    // DEPOSIT WITH INTENT  ➜ relayed, signed off-chain
+    // ─────────────────────────────────────────
+    /// Submitted by a relayer (`user`) for the owner of `user_asset_account`,
+    /// who signed a `DepositIntent` checked by the ed25519 instruction right
+    /// before this one and delegated the amount to the vault authority.
+    pub fn deposit_with_intent<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deposit<'info>>,
+        amount: u64,
+        min_shares: u64,
+        nonce: u64,
+        expiry: i64,
+    ) -> Result<()> {
+        let owner = ctx
+            .accounts
+            .user_asset_account
+            .as_ref()
+            .ok_or(VaultError::AssetAccountRequired)?
+            .owner;
+        // the relayer must not redirect the shares
+        require_keys_eq!(
+            ctx.accounts.receiver.key(),
+            owner,
+            VaultError::InvalidIntent
+        );
+        require!(
+            Clock::get()?.unix_timestamp <= expiry,
+            VaultError::IntentExpired
+        );
+
+        let intent = DepositIntent {
+            vault: ctx.accounts.vault_state.key(),
+            amount,
+            min_shares,
+            nonce,
+            expiry,
+        };
+        let instructions = ctx
+            .accounts
+            .instructions
+            .as_ref()
+            .ok_or(VaultError::InvalidIntent)?;
+        verify_ed25519_signature(instructions, &owner, &intent.try_to_vec()?)?;
+
+        let intent_nonce = ctx
+            .accounts
+            .intent_nonce
+            .as_mut()
+            .ok_or(VaultError::InvalidIntent)?;
+        require!(
+            nonce == intent_nonce.next_nonce,
+            VaultError::IntentNonceUsed
+        );
+        intent_nonce.vault = intent.vault;
+        intent_nonce.owner = owner;
+        intent_nonce.next_nonce = nonce.checked_add(1).ok_or(VaultError::Overflow)?;
+        intent_nonce.bump = ctx.bumps.intent_nonce.ok_or(VaultError::InvalidIntent)?;
+
+        let shares = deposit_assets(ctx, amount, true)?;
+        require!(shares >= min_shares, VaultError::SlippageExceeded);
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // RECURRING DEPOSITS  ➜ DCA schedule run by keepers
+    // ─────────────────────────────────────────
+    /// Schedules `amount` every `interval` seconds until `end_ts`, the first
+    /// one due right away. The owner must also approve the vault authority as
+    /// delegate on `user_asset_account`.
+    pub fn create_recurring_deposit(
+        ctx: Context<CreateRecurringDeposit>,
+        amount: u64,
+        interval: i64,
+        end_ts: i64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(amount > 0, VaultError::InvalidAmount);
+        require!(
+            interval > 0 && end_ts > now,
+            VaultError::InvalidRecurringDeposit
+        );
+
+        let recurring = &mut ctx.accounts.recurring_deposit;
+        recurring.vault = ctx.accounts.vault_state.key();
+        recurring.owner = ctx.accounts.owner.key();
+        recurring.source = ctx.accounts.user_asset_account.key();
+        recurring.amount = amount;
+        recurring.interval = interval;
+        recurring.next_ts = now;
+        recurring.end_ts = end_ts;
+        recurring.bump = ctx.bumps.recurring_deposit;
+        Ok(())
+    }
+
+    pub fn cancel_recurring_deposit(_ctx: Context<CancelRecurringDeposit>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Permissionless crank: runs the schedule's due deposit for its owner
+    /// and pays the keeper (`user`) `keeper_bounty_bps` of it. Missed slots
+    /// are not made up, and the schedule closes after its last deposit.
+    pub fn execute_recurring_deposit<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deposit<'info>>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let source = ctx
+            .accounts
+            .user_asset_account
+            .as_ref()
+            .ok_or(VaultError::AssetAccountRequired)?
+            .to_account_info();
+        let recurring = ctx
+            .accounts
+            .recurring_deposit
+            .as_mut()
+            .ok_or(VaultError::InvalidRecurringDeposit)?;
+        require_keys_eq!(
+            recurring.source,
+            source.key(),
+            VaultError::InvalidRecurringDeposit
+        );
+        require!(
+            now >= recurring.next_ts && recurring.next_ts <= recurring.end_ts,
+            VaultError::RecurringDepositNotDue
+        );
+        let amount = recurring.amount;
+        // one deposit per crank: slots missed while no keeper ran are skipped
+        let slots = (now - recurring.next_ts) / recurring.interval + 1;
+        recurring.next_ts = slots
+            .checked_mul(recurring.interval)
+            .and_then(|skip| recurring.next_ts.checked_add(skip))
+            .ok_or(VaultError::Overflow)?;
+        let next_ts = recurring.next_ts;
+        // the schedule's last deposit returns its rent to the owner
+        if next_ts > recurring.end_ts {
+            recurring.close(ctx.accounts.receiver.to_account_info())?;
+        }
 
         // ╭──────────────────────────────────╮
-        // │  Update state                    │
+        // │  Pay the keeper's bounty         │
         // ╰──────────────────────────────────╯
-        state.total_asset = state
-            .total_asset
-            .checked_add(amount)
-            .ok_or(VaultError::Overflow)?;
-        state.total_shares = state
-            .total_shares
-            .checked_add(shares_to_mint)
+        let bounty = mul_div(
+            amount,
+            ctx.accounts.vault_state.keeper_bounty_bps as u64,
+            MAX_BPS,
+        )?;
+        if bounty > 0 {
+            let keeper_asset_account = ctx
+                .accounts
+                .keeper_asset_account
+                .as_ref()
+                .ok_or(VaultError::InvalidRecurringDeposit)?;
+            let state_key = ctx.accounts.vault_state.key();
+            let vault_authority_seeds: &[&[u8]] = &[
+                b"vault_authority",
+                state_key.as_ref(),
+                &[ctx.bumps.vault_authority],
+            ];
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: source,
+                        to: keeper_asset_account.to_account_info(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                    },
+                    &[vault_authority_seeds],
+                ),
+                bounty,
+            )?;
+        }
+
+        emit!(RecurringDepositExecutedEvent {
+            vault: ctx.accounts.vault_state.key(),
+            owner: ctx.accounts.receiver.key(),
+            keeper: ctx.accounts.user.key(),
+            amount,
+            keeper_bounty: bounty,
+            next_ts,
+        });
+        let deposit_amount = amount.checked_sub(bounty).ok_or(VaultError::Overflow)?;
+        deposit_assets(ctx, deposit_amount, true).map(|_| ())
+    }
+
+    // ─────────────────────────────────────────
+    // BATCH DEPOSIT  ➜ one funding source, many recipients
+    // ─────────────────────────────────────────
+    /// Deposits `amounts[i]` for the owner of the i-th recipient, all paid
+    /// from `user_asset_account`. Each recipient passes, as remaining
+    /// accounts, its share account, `position` and `user_position` (the
+    /// program id leaves either out; PDAs are created when missing) and then
+    /// its reward checkpoint pairs, booked like `deposit_asset_a`.
+    pub fn batch_deposit<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchDeposit<'info>>,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        require!(!amounts.is_empty(), VaultError::InvalidAmount);
+        // [share_account, position, user_position, (pool, checkpoint)...]
+        let group = 3 + 2 * ctx.accounts.vault_state.reward_pool_count as usize;
+        require!(
+            ctx.remaining_accounts.len() == amounts.len() * group,
+            VaultError::BatchLengthMismatch
+        );
+        require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        require!(
+            !ctx.accounts.vault_state.epoch_mode,
+            VaultError::EpochModeActive
+        );
+
+        // ╭──────────────────────────────────╮
+        // │  Pull the whole batch at once    │
+        // ╰──────────────────────────────────╯
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |sum, amount| sum.checked_add(*amount))
             .ok_or(VaultError::Overflow)?;
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_asset_account.to_account_info(),
+                    to: ctx.accounts.vault_asset_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            total,
+        )?;
 
         // ╭──────────────────────────────────╮
-        // │  Emit event                      │
+        // │  Mint to each recipient          │
         // ╰──────────────────────────────────╯
-        emit!(DepositEvent {
-            user: ctx.accounts.user.key(),
-            asset_amount: amount,
-            shares_minted: shares_to_mint,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
-        msg!("DepositEvent finished!");
+        let state = &mut ctx.accounts.vault_state;
+        let state_key = state.key();
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let now = Clock::get()?.unix_timestamp;
+        let funder = ctx.accounts.user_asset_account.owner;
+        let payer = ctx.accounts.user.to_account_info();
+        let system = ctx.accounts.system_program.to_account_info();
+        for (accounts, &amount) in ctx.remaining_accounts.chunks_exact(group).zip(&amounts) {
+            require!(amount > 0, VaultError::InvalidAmount);
+            let share_account =
+                InterfaceAccount::<token_interface::TokenAccount>::try_from(&accounts[0])?;
+            require_keys_eq!(
+                share_account.mint,
+                state.share_mint,
+                VaultError::InvalidMint
+            );
+            let receiver = share_account.owner;
+
+            let shares_to_mint = state.convert_to_shares(amount)?;
+            require!(shares_to_mint > 0, VaultError::RoundingError);
+            token_interface::mint_to(
+                CpiContext::new_with_signer(
+                    ctx.accounts.share_token_program.to_account_info(),
+                    token_interface::MintTo {
+                        mint: ctx.accounts.share_mint.to_account_info(),
+                        to: accounts[0].clone(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                    },
+                    &[vault_authority_seeds],
+                ),
+                shares_to_mint,
+            )?;
+            sync_reward_checkpoints(
+                &accounts[3..],
+                state,
+                receiver,
+                share_account.amount,
+                share_account
+                    .amount
+                    .checked_add(shares_to_mint)
+                    .ok_or(VaultError::Overflow)?,
+            )?;
+
+            state.total_asset = state
+                .total_asset
+                .checked_add(amount)
+                .ok_or(VaultError::Overflow)?;
+            state.total_shares = state
+                .total_shares
+                .checked_add(shares_to_mint)
+                .ok_or(VaultError::Overflow)?;
+
+            if state.lockup_period > 0 {
+                require!(accounts[1].key() != crate::ID, VaultError::PositionRequired);
+                let (mut position, _) = init_pda_if_needed::<Position>(
+                    &accounts[1],
+                    &[b"position", state_key.as_ref(), receiver.as_ref()],
+                    _POSITION_SIZE,
+                    &payer,
+                    &system,
+                )?;
+                // only the receiver's own deposits may push their unlock time out
+                lock_new_shares(
+                    &mut position,
+                    state,
+                    receiver,
+                    shares_to_mint,
+                    funder == receiver,
+                    now,
+                )?;
+                position.exit(&crate::ID)?;
+            }
+            if accounts[2].key() != crate::ID {
+                let (mut user_position, bump) = init_pda_if_needed::<UserPosition>(
+                    &accounts[2],
+                    &[b"user_position", state_key.as_ref(), receiver.as_ref()],
+                    _USER_POSITION_SIZE,
+                    &payer,
+                    &system,
+                )?;
+                user_position.vault = state_key;
+                user_position.owner = receiver;
+                user_position.bump = bump;
+                if funder == receiver {
+                    user_position.record_deposit(amount, shares_to_mint)?;
+                }
+                user_position.exit(&crate::ID)?;
+            }
+
+            emit!(DepositEvent {
+                sender: ctx.accounts.user.key(),
+                owner: funder,
+                receiver,
+                asset_amount: amount,
+                shares_minted: shares_to_mint,
+                timestamp: now,
+            });
+        }
+        check_deposit_cap(state, ctx.accounts.asset_oracle.as_deref())?;
+        PriceHistory::snapshot(ctx.accounts.price_history.as_mut(), state, now)?;
+        SharePriceFeed::publish(ctx.accounts.share_price_feed.as_mut(), state)?;
         Ok(())
     }
 
+    // ─────────────────────────────────────────
+    // DEPOSIT SOL  ➜ wrap lamports, mint vault shares
+    // ─────────────────────────────────────────
+    /// `deposit_asset_a` for native-mint vaults, paid in lamports instead of
+    /// wSOL; `user_asset_account` is left out.
+    pub fn deposit_sol<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deposit<'info>>,
+        lamports: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.user_asset_account.is_none(),
+            VaultError::NotNativeVault
+        );
+        deposit_asset_a(ctx, lamports)
+    }
+
     // ─────────────────────────────────────────
     // WITHDRAW ASSET A  ➜ burn vault shares
     // ─────────────────────────────────────────
-    pub fn withdraw_asset_a(ctx: Context<Withdraw>, shares: u64) -> Result<()> {
+    /// With `close_if_empty`, a share account emptied by this withdrawal is
+    /// closed and its rent refunded to the owner (who must then be `user`).
+    pub fn withdraw_asset_a<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>,
+        shares: u64,
+        close_if_empty: bool,
+    ) -> Result<()> {
         require!(shares > 0, VaultError::InvalidAmount);
         require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        require!(
+            !ctx.accounts.vault_state.epoch_mode,
+            VaultError::EpochModeActive
+        );
 
         let state = &mut ctx.accounts.vault_state;
         require!(shares <= state.total_shares, VaultError::InvalidShares);
+        let now = Clock::get()?.unix_timestamp;
+        // `user` is the holder, their SPL delegate, or an approved operator
+        let owner = ctx.accounts.user_share_account.owner;
 
         // ╭──────────────────────────────────╮
-        // │  Burn shares from user           │
+        // │  Lockup: block or penalise       │
         // ╰──────────────────────────────────╯
-        let cpi_accs = Burn {
-            mint: ctx.accounts.share_mint.to_account_info(),
-            from: ctx.accounts.user_share_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
+        let locked_burned = match ctx.accounts.position.as_mut() {
+            Some(position) => release_locked_shares(
+                position,
+                ctx.accounts.user_share_account.amount,
+                shares,
+                now,
+            )?,
+            None => {
+                require!(state.lockup_period == 0, VaultError::PositionRequired);
+                0
+            }
         };
-        token::burn(
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accs),
-            shares,
-        )?;
+        require!(
+            locked_burned == 0 || state.early_exit_penalty_bps > 0,
+            VaultError::SharesLocked
+        );
 
         // ╭──────────────────────────────────╮
         // │  Calculate amount to send back   │
         // ╰──────────────────────────────────╯
-        let asset_amount: u64 = (shares as u128)
-            .checked_mul(state.total_asset as u128)
-            .unwrap()
-            .checked_div(state.total_shares as u128)
-            .unwrap() as u64;
+        let gross_amount = state.convert_to_assets(shares)?;
+
+        // the penalty on locked shares stays in the vault, so it is shared by
+        // the remaining holders through the share price, minus the protocol's cut
+        let penalty = mul_div(
+            state.convert_to_assets(locked_burned)?,
+            state.early_exit_penalty_bps as u64,
+            MAX_BPS,
+        )?;
+        let protocol_fee = ctx.accounts.protocol_config.protocol_cut(penalty)?;
+        let asset_amount = gross_amount
+            .checked_sub(penalty)
+            .ok_or(VaultError::Overflow)?;
 
         require!(asset_amount > 0, VaultError::RoundingError);
 
         // ╭──────────────────────────────────╮
-        // │  Transfer Asset A to user        │
+        // │  Burn shares from owner          │
         // ╰──────────────────────────────────╯
         let state_key = state.key(); // own the Pubkey for the rest of the function
         let vault_authority_seeds: &[&[u8]] = &[
@@ -164,89 +775,3716 @@ pub mod reza_vault {
         ];
         let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds]; // &[&[u8]] → &[&[&[u8]]]
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.vault_asset_account.to_account_info(),
-            to: ctx.accounts.user_asset_account.to_account_info(),
-            authority: ctx.accounts.vault_authority.to_account_info(),
-        };
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts,
-                signer_seeds,
-            ),
-            asset_amount,
+        match ctx.accounts.operator.as_mut() {
+            // vault-level operator: the vault authority is the SPL delegate
+            // and the operator's own allowance is spent
+            Some(operator) => {
+                operator.allowance = operator
+                    .allowance
+                    .checked_sub(shares)
+                    .ok_or(VaultError::AllowanceExceeded)?;
+                let cpi_accs = token_interface::Burn {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    from: ctx.accounts.user_share_account.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                };
+                token_interface::burn(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.share_token_program.to_account_info(),
+                        cpi_accs,
+                        signer_seeds,
+                    ),
+                    shares,
+                )?;
+            }
+            // owner or SPL delegate; the token program spends the delegation
+            None => {
+                let cpi_accs = token_interface::Burn {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    from: ctx.accounts.user_share_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                };
+                token_interface::burn(
+                    CpiContext::new(ctx.accounts.share_token_program.to_account_info(), cpi_accs),
+                    shares,
+                )?;
+            }
+        }
+        if close_if_empty && ctx.accounts.user_share_account.amount == shares {
+            require_keys_eq!(owner, ctx.accounts.user.key(), VaultError::InvalidOwner);
+            token_interface::close_account(CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_interface::CloseAccount {
+                    account: ctx.accounts.user_share_account.to_account_info(),
+                    destination: ctx.accounts.user.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ))?;
+        }
+
+        // ╭──────────────────────────────────╮
+        // │  Reward checkpoints              │
+        // ╰──────────────────────────────────╯
+        let old_balance = ctx.accounts.user_share_account.amount;
+        sync_reward_checkpoints(
+            ctx.remaining_accounts,
+            state,
+            owner,
+            old_balance,
+            old_balance.saturating_sub(shares),
         )?;
 
+        // ╭──────────────────────────────────╮
+        // │  Redemption gate                 │
+        // ╰──────────────────────────────────╯
+        if !state.gate_allows(asset_amount, now)? {
+            // past the gate: either fail, or park the (already burned)
+            // shares in the holder's queue when they passed one
+            let queued = ctx
+                .accounts
+                .queued_withdrawal
+                .as_mut()
+                .ok_or(VaultError::RedemptionGateExceeded)?;
+            require!(locked_burned == 0, VaultError::SharesLocked);
+            queued.vault = state.key();
+            queued.owner = owner;
+            queued.shares = queued
+                .shares
+                .checked_add(shares)
+                .ok_or(VaultError::Overflow)?;
+            queued.queued_at = now;
+            state.queued_redeem_shares = state
+                .queued_redeem_shares
+                .checked_add(shares)
+                .ok_or(VaultError::Overflow)?;
+
+            emit!(WithdrawalQueuedEvent {
+                user: owner,
+                shares,
+                timestamp: now,
+            });
+            record_gate_hit(state, now);
+
+            // `withdraw_sol` created the (empty) scratch account; return its rent
+            if let Some(unwrap_account) = ctx.accounts.sol_unwrap_account.as_ref() {
+                token::close_account(CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::CloseAccount {
+                        account: unwrap_account.to_account_info(),
+                        destination: ctx.accounts.user.to_account_info(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                    },
+                    signer_seeds,
+                ))?;
+            }
+            return Ok(());
+        }
+        state.record_outflow(asset_amount)?;
+
+        // ╭──────────────────────────────────╮
+        // │  Cost basis (optional)           │
+        // ╰──────────────────────────────────╯
+        // queued shares are booked by `process_queued_withdrawal` once paid
+        if let Some(user_position) = ctx.accounts.user_position.as_mut() {
+            user_position.record_withdrawal(shares, asset_amount)?;
+        }
+
+        // ╭──────────────────────────────────╮
+        // │  Transfer Asset A to receiver    │
+        // ╰──────────────────────────────────╯
+        let receiver = match ctx.accounts.receiver_asset_account.as_ref() {
+            Some(receiver_asset_account) => {
+                let cpi_accounts = Transfer {
+                    from: ctx.accounts.vault_asset_account.to_account_info(),
+                    to: receiver_asset_account.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                };
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        cpi_accounts,
+                        signer_seeds,
+                    ),
+                    asset_amount,
+                )?;
+                receiver_asset_account.owner
+            }
+            // native vault paid out in lamports: move the wSOL into a scratch
+            // account and close it, which releases the lamports to `user`
+            None => {
+                let (Some(unwrap_account), Some(sol_receiver)) = (
+                    ctx.accounts.sol_unwrap_account.as_ref(),
+                    ctx.accounts.sol_receiver.as_ref(),
+                ) else {
+                    return err!(VaultError::AssetAccountRequired);
+                };
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.vault_asset_account.to_account_info(),
+                            to: unwrap_account.to_account_info(),
+                            authority: ctx.accounts.vault_authority.to_account_info(),
+                        },
+                        signer_seeds,
+                    ),
+                    asset_amount,
+                )?;
+                token::close_account(CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::CloseAccount {
+                        account: unwrap_account.to_account_info(),
+                        destination: ctx.accounts.user.to_account_info(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                    },
+                    signer_seeds,
+                ))?;
+                // `user` got the scratch account's rent back plus the payout;
+                // forward the payout when it is meant for another wallet
+                if sol_receiver.key() != ctx.accounts.user.key() {
+                    system_program::transfer(
+                        CpiContext::new(
+                            ctx.accounts.system_program.to_account_info(),
+                            system_program::Transfer {
+                                from: ctx.accounts.user.to_account_info(),
+                                to: sol_receiver.to_account_info(),
+                            },
+                        ),
+                        asset_amount,
+                    )?;
+                }
+                sol_receiver.key()
+            }
+        };
+
         // ╭──────────────────────────────────╮
         // │  Update state                    │
         // ╰──────────────────────────────────╯
         state.total_asset = state
             .total_asset
             .checked_sub(asset_amount)
+            .and_then(|v| v.checked_sub(protocol_fee))
             .ok_or(VaultError::Overflow)?;
         state.total_shares = state
             .total_shares
             .checked_sub(shares)
             .ok_or(VaultError::Overflow)?;
+        state.protocol_fees_accrued = state
+            .protocol_fees_accrued
+            .checked_add(protocol_fee)
+            .ok_or(VaultError::Overflow)?;
+        PriceHistory::snapshot(ctx.accounts.price_history.as_mut(), state, now)?;
+        SharePriceFeed::publish(ctx.accounts.share_price_feed.as_mut(), state)?;
 
         emit!(WithdrawEvent {
-            user: ctx.accounts.user.key(),
+            sender: ctx.accounts.user.key(),
+            owner,
+            receiver,
             shares_burned: shares,
             asset_amount,
-            timestamp: Clock::get()?.unix_timestamp,
+            early_exit_penalty: penalty,
+            timestamp: now,
         });
         msg!("WithdrawEvent finished!");
         Ok(())
     }
 
     // ─────────────────────────────────────────
-    // ADMIN: pause / unpause vault
+    // WITHDRAW SOL  ➜ burn vault shares, unwrap to lamports
     // ─────────────────────────────────────────
-    pub fn set_pause(ctx: Context<AdminAction>, pause: bool) -> Result<()> {
-        ctx.accounts.vault_state.paused = pause;
+    /// `withdraw_asset_a` for native-mint vaults, paid out in lamports to
+    /// `sol_receiver`; pass `native_mint`, `sol_unwrap_account` and
+    /// `sol_receiver` instead of `receiver_asset_account`.
+    pub fn withdraw_sol<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>,
+        shares: u64,
+        close_if_empty: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.receiver_asset_account.is_none(),
+            VaultError::NotNativeVault
+        );
+        withdraw_asset_a(ctx, shares, close_if_empty)
+    }
+
+    // ─────────────────────────────────────────
+    // HOLDER: approve / revoke a redemption operator
+    // ─────────────────────────────────────────
+    /// Lets `operator` redeem up to `allowance` of the holder's shares through
+    /// `withdraw_asset_a`. The vault authority becomes the SPL delegate of the
+    /// share account for that amount, replacing any earlier delegate. A holder
+    /// has one operator per vault, since the share account has one delegation;
+    /// approving another operator replaces the current one.
+    pub fn approve_operator(ctx: Context<ApproveOperator>, allowance: u64) -> Result<()> {
+        let operator = &mut ctx.accounts.operator_allowance;
+        operator.vault = ctx.accounts.vault_state.key();
+        operator.owner = ctx.accounts.owner.key();
+        operator.operator = ctx.accounts.operator.key();
+        operator.allowance = allowance;
+        operator.bump = ctx.bumps.operator_allowance;
+
+        token_interface::approve(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_interface::Approve {
+                    to: ctx.accounts.user_share_account.to_account_info(),
+                    delegate: ctx.accounts.vault_authority.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            allowance,
+        )?;
+
+        emit!(OperatorApprovedEvent {
+            vault: operator.vault,
+            owner: operator.owner,
+            operator: operator.operator,
+            allowance,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
         Ok(())
     }
-}
 
-// ╭────────────────────────────────────────────
-// │              ACCOUNT STATE                 │
-// ╰────────────────────────────────────────────
-#[account]
-pub struct VaultState {
-    /*  8 */ pub asset_mint: Pubkey,
-    /* 40 */ pub share_mint: Pubkey,
-    /* 72 */ pub vault_authority: Pubkey,
-    /*104 */ pub vault_asset_account: Pubkey,
-    /*136 */ pub admin: Pubkey,
-    /*168 */ pub total_asset: u64,
-    /*176 */ pub total_shares: u64,
-    /*184 */ pub paused: bool,
-    /*185 */ _padding: [u8; 7],
-}
-const _VAULT_STATE_SIZE: usize = 8 + 32 * 5 + 8 + 8 + 1 + 7; // = 185
+    /// Closes the operator's allowance and clears the share account's delegate.
+    pub fn revoke_operator(ctx: Context<RevokeOperator>) -> Result<()> {
+        token_interface::revoke(CpiContext::new(
+            ctx.accounts.share_token_program.to_account_info(),
+            token_interface::Revoke {
+                source: ctx.accounts.user_share_account.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        ))?;
 
-// ╭────────────────────────────────────────────
-// │                EVENTS                      │
-// ╰────────────────────────────────────────────
-#[event]
-pub struct DepositEvent {
-    pub user: Pubkey,
-    pub asset_amount: u64,
-    pub shares_minted: u64,
-    pub timestamp: i64,
-}
+        emit!(OperatorApprovedEvent {
+            vault: ctx.accounts.vault_state.key(),
+            owner: ctx.accounts.owner.key(),
+            operator: ctx.accounts.operator_allowance.operator,
+            allowance: 0,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
 
-#[event]
-pub struct WithdrawEvent {
-    pub user: Pubkey,
-    pub shares_burned: u64,
-    pub asset_amount: u64,
-    pub timestamp: i64,
-}
+    // ─────────────────────────────────────────
+    // FLASH LOANS: borrow idle Asset A within one transaction
+    // ─────────────────────────────────────────
+    /// Lends `amount` from `vault_asset_account`. The same transaction must
+    /// call `flash_repay` for this vault later on, which is checked here via
+    /// the instructions sysvar.
+    pub fn flash_borrow(ctx: Context<FlashBorrow>, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        require!(
+            !ctx.accounts.vault_state.flash_paused,
+            VaultError::FlashLoansPaused
+        );
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        require!(
+            ctx.accounts.vault_state.flash_loan_amount == 0,
+            VaultError::FlashLoanActive
+        );
 
-// ╭────────────────────────────────────────────
-// │                ERRORS                      │
+        // ╭──────────────────────────────────╮
+        // │  Repayment must follow           │
+        // ╰──────────────────────────────────╯
+        let instructions = ctx.accounts.instructions.to_account_info();
+        let current = load_current_index_checked(&instructions)? as usize;
+        // a CPI'd borrow could hide the repay behind another top-level program
+        let this_ix = load_instruction_at_checked(current, &instructions)?;
+        require_keys_eq!(
+            this_ix.program_id,
+            crate::ID,
+            VaultError::FlashLoanNotRepaid
+        );
+        let state_key = ctx.accounts.vault_state.key();
+        let mut index = current + 1;
+        loop {
+            let ix = load_instruction_at_checked(index, &instructions)
+                .map_err(|_| error!(VaultError::FlashLoanNotRepaid))?;
+            if ix.program_id == crate::ID
+                && ix.data.starts_with(instruction::FlashRepay::DISCRIMINATOR)
+                && ix.accounts.first().map(|meta| meta.pubkey) == Some(state_key)
+            {
+                break;
+            }
+            index += 1;
+        }
+
+        // ╭──────────────────────────────────╮
+        // │  Lend idle Asset A               │
+        // ╰──────────────────────────────────╯
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault_asset_account.to_account_info(),
+                    to: ctx.accounts.borrower_asset_account.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                },
+                &[vault_authority_seeds],
+            ),
+            amount,
+        )?;
+        ctx.accounts.vault_state.flash_loan_amount = amount;
+        Ok(())
+    }
+
+    /// Pays back the open flash loan plus `flash_fee_bps`; the fee is added to
+    /// `total_asset`, so it goes to the shareholders.
+    pub fn flash_repay(ctx: Context<FlashRepay>) -> Result<()> {
+        let state = &mut ctx.accounts.vault_state;
+        let amount = state.flash_loan_amount;
+        require!(amount > 0, VaultError::NoFlashLoan);
+        let fee = mul_div(amount, state.flash_fee_bps as u64, MAX_BPS)?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.borrower_asset_account.to_account_info(),
+                    to: ctx.accounts.vault_asset_account.to_account_info(),
+                    authority: ctx.accounts.borrower.to_account_info(),
+                },
+            ),
+            amount.checked_add(fee).ok_or(VaultError::Overflow)?,
+        )?;
+
+        // the fee goes to the holders, minus the protocol's cut
+        let protocol_fee = ctx.accounts.protocol_config.protocol_cut(fee)?;
+        state.total_asset = state
+            .total_asset
+            .checked_add(fee - protocol_fee)
+            .ok_or(VaultError::Overflow)?;
+        state.protocol_fees_accrued = state
+            .protocol_fees_accrued
+            .checked_add(protocol_fee)
+            .ok_or(VaultError::Overflow)?;
+        state.flash_loan_amount = 0;
+        PriceHistory::snapshot(
+            ctx.accounts.price_history.as_mut(),
+            state,
+            Clock::get()?.unix_timestamp,
+        )?;
+        SharePriceFeed::publish(ctx.accounts.share_price_feed.as_mut(), state)?;
+
+        emit!(FlashLoanEvent {
+            vault: state.key(),
+            borrower: ctx.accounts.borrower.key(),
+            amount,
+            fee,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // VIEW: a holder's cost basis and realized PnL
+    // ─────────────────────────────────────────
+    /// Read-only; the result comes back as return data (`.view()` in TS).
+    pub fn get_user_position(ctx: Context<ViewUserPosition>) -> Result<UserPositionView> {
+        Ok(ctx.accounts.user_position.view())
+    }
+
+    // ─────────────────────────────────────────
+    // PRICE HISTORY  ➜ share price snapshots & TWAP
+    // ─────────────────────────────────────────
+    /// Starts the vault's ring buffer with a snapshot of the current price;
+    /// further snapshots are taken at most once per `interval` seconds. From
+    /// then on every instruction that moves the share price must pass it.
+    pub fn create_price_history(ctx: Context<CreatePriceHistory>, interval: i64) -> Result<()> {
+        require!(interval > 0, VaultError::InvalidPriceInterval);
+        let state = &mut ctx.accounts.vault_state;
+        state.has_price_history = true;
+        let history = &mut ctx.accounts.price_history;
+        history.vault = state.key();
+        history.interval = interval;
+        history.head = 0;
+        history.len = 0;
+        history.bump = ctx.bumps.price_history;
+        history.snapshots = [PriceSnapshot::default(); PRICE_HISTORY_LEN];
+        history.record(
+            Clock::get()?.unix_timestamp,
+            state.total_asset,
+            state.total_shares,
+        );
+        Ok(())
+    }
+
+    pub fn set_price_history_interval(
+        ctx: Context<PriceHistoryAdmin>,
+        interval: i64,
+    ) -> Result<()> {
+        require!(interval > 0, VaultError::InvalidPriceInterval);
+        ctx.accounts.price_history.interval = interval;
+        Ok(())
+    }
+
+    /// Permissionless: records a snapshot if the interval has passed, so the
+    /// history keeps moving while the vault is quiet.
+    pub fn sync_price_history(ctx: Context<SyncPriceHistory>) -> Result<()> {
+        let state = &ctx.accounts.vault_state;
+        ctx.accounts.price_history.record(
+            Clock::get()?.unix_timestamp,
+            state.total_asset,
+            state.total_shares,
+        );
+        Ok(())
+    }
+
+    /// Time-weighted assets per share (scaled by `PRICE_PRECISION`) over the
+    /// last `window` seconds. Read-only; use `.view()` in TS.
+    pub fn get_twap(ctx: Context<ViewPriceHistory>, window: i64) -> Result<u64> {
+        ctx.accounts
+            .price_history
+            .twap(Clock::get()?.unix_timestamp, window)
+    }
+
+    // ─────────────────────────────────────────
+    // SHARE PRICE FEED  ➜ oracle-style account for integrators
+    // ─────────────────────────────────────────
+    /// Opens the vault's feed; readers reject it once it is more than
+    /// `max_staleness_slots` behind. From then on every instruction that moves
+    /// the share price must pass the feed.
+    pub fn create_share_price_feed(
+        ctx: Context<CreateSharePriceFeed>,
+        max_staleness_slots: u64,
+    ) -> Result<()> {
+        require!(max_staleness_slots > 0, VaultError::InvalidFeedStaleness);
+        let state = &mut ctx.accounts.vault_state;
+        state.has_share_price_feed = true;
+        let feed = &mut ctx.accounts.share_price_feed;
+        feed.vault = state.key();
+        feed.share_mint = state.share_mint;
+        feed.expo = SharePriceFeed::EXPO;
+        feed.max_staleness_slots = max_staleness_slots;
+        feed.bump = ctx.bumps.share_price_feed;
+        feed.price = share_price(state.total_asset, state.total_shares)?;
+        feed.update(state)
+    }
+
+    pub fn set_feed_staleness(
+        ctx: Context<SharePriceFeedAdmin>,
+        max_staleness_slots: u64,
+    ) -> Result<()> {
+        require!(max_staleness_slots > 0, VaultError::InvalidFeedStaleness);
+        ctx.accounts.share_price_feed.max_staleness_slots = max_staleness_slots;
+        Ok(())
+    }
+
+    /// Permissionless: republishes the current price, e.g. to keep a quiet
+    /// vault's feed fresh.
+    pub fn refresh_share_price_feed(ctx: Context<RefreshSharePriceFeed>) -> Result<()> {
+        ctx.accounts
+            .share_price_feed
+            .update(&ctx.accounts.vault_state)
+    }
+
+    // ─────────────────────────────────────────
+    // ORACLE NAV  ➜ Asset A priced in USD
+    // ─────────────────────────────────────────
+    /// Attaches an Asset A / USD price account in the Pyth `PriceUpdateV2`
+    /// layout; prices older than `max_age` seconds or with a confidence wider
+    /// than `max_conf_bps` of the price are rejected.
+    pub fn set_oracle(
+        ctx: Context<AdminAction>,
+        oracle: Pubkey,
+        max_age: i64,
+        max_conf_bps: u16,
+    ) -> Result<()> {
+        require!(max_age > 0, VaultError::InvalidOracle);
+        require!(max_conf_bps as u64 <= MAX_BPS, VaultError::InvalidBps);
+        let state = &mut ctx.accounts.vault_state;
+        state.oracle = oracle;
+        state.oracle_max_age = max_age;
+        state.oracle_max_conf_bps = max_conf_bps;
+        Ok(())
+    }
+
+    /// Caps the USD value of the vault's assets (pending epoch deposits
+    /// included) after any deposit; 0 removes the cap.
+    pub fn set_deposit_cap_usd(ctx: Context<AdminAction>, deposit_cap_usd: u64) -> Result<()> {
+        let state = &mut ctx.accounts.vault_state;
+        require!(
+            deposit_cap_usd == 0 || state.oracle != Pubkey::default(),
+            VaultError::OracleRequired
+        );
+        state.deposit_cap_usd = deposit_cap_usd;
+        Ok(())
+    }
+
+    /// TVL and the value of one whole share in USD (`USD_DECIMALS`).
+    /// Read-only; use `.view()` in TS.
+    pub fn get_nav_usd(ctx: Context<ViewNavUsd>) -> Result<NavUsdView> {
+        let state = &ctx.accounts.vault_state;
+        let price = OraclePrice::load(&ctx.accounts.oracle, state, Clock::get()?.unix_timestamp)?;
+        let one_share = 10u64
+            .checked_pow(state.asset_decimals as u32)
+            .ok_or(VaultError::Overflow)?;
+        let assets_per_share = mul_div(
+            one_share,
+            share_price(state.total_asset, state.total_shares)?,
+            PRICE_PRECISION,
+        )?;
+        Ok(NavUsdView {
+            tvl_usd: price.usd_value(state.total_asset, state.asset_decimals)?,
+            share_price_usd: price.usd_value(assets_per_share, state.asset_decimals)?,
+            oracle_price: price.price,
+            oracle_expo: price.expo,
+            oracle_publish_time: price.publish_time,
+        })
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: pause / unpause vault
+    // ─────────────────────────────────────────
+    pub fn set_pause(ctx: Context<AdminAction>, pause: bool) -> Result<()> {
+        ctx.accounts.vault_state.paused = pause;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: flash-loan fee and pause
+    // ─────────────────────────────────────────
+    pub fn set_flash_loan_fee(ctx: Context<AdminAction>, flash_fee_bps: u16) -> Result<()> {
+        require!(flash_fee_bps as u64 <= MAX_BPS, VaultError::InvalidBps);
+        ctx.accounts.vault_state.flash_fee_bps = flash_fee_bps;
+        Ok(())
+    }
+
+    /// Stops flash loans only; deposits and withdrawals keep working.
+    pub fn set_flash_pause(ctx: Context<AdminAction>, pause: bool) -> Result<()> {
+        ctx.accounts.vault_state.flash_paused = pause;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: compliance freeze / thaw of a holder's shares
+    // ─────────────────────────────────────────
+    pub fn freeze_holder(ctx: Context<HolderFreeze>, reason_code: u16) -> Result<()> {
+        let state_key = ctx.accounts.vault_state.key();
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        token_interface::freeze_account(CpiContext::new_with_signer(
+            ctx.accounts.share_token_program.to_account_info(),
+            token_interface::FreezeAccount {
+                account: ctx.accounts.holder_share_account.to_account_info(),
+                mint: ctx.accounts.share_mint.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            &[vault_authority_seeds],
+        ))?;
+
+        emit!(HolderFrozenEvent {
+            vault: state_key,
+            share_account: ctx.accounts.holder_share_account.key(),
+            holder: ctx.accounts.holder_share_account.owner,
+            reason_code,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn thaw_holder(ctx: Context<HolderFreeze>, reason_code: u16) -> Result<()> {
+        let state_key = ctx.accounts.vault_state.key();
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        token_interface::thaw_account(CpiContext::new_with_signer(
+            ctx.accounts.share_token_program.to_account_info(),
+            token_interface::ThawAccount {
+                account: ctx.accounts.holder_share_account.to_account_info(),
+                mint: ctx.accounts.share_mint.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            &[vault_authority_seeds],
+        ))?;
+
+        emit!(HolderThawedEvent {
+            vault: state_key,
+            share_account: ctx.accounts.holder_share_account.key(),
+            holder: ctx.accounts.holder_share_account.owner,
+            reason_code,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: rename / re-point the share token
+    // ─────────────────────────────────────────
+    pub fn update_share_metadata(
+        ctx: Context<UpdateShareMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        let share_mint = ctx.accounts.share_mint.to_account_info();
+        let vault_authority_key = ctx.accounts.vault_authority.key();
+        let updated = share_metadata(share_mint.key(), vault_authority_key, name, symbol, uri)?;
+
+        // top up rent first if the new strings grow the mint account
+        let new_len = {
+            let data = share_mint.try_borrow_data()?;
+            let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+            let current = mint.get_variable_len_extension::<TokenMetadata>()?;
+            data.len() - current.tlv_size_of()? + updated.tlv_size_of()?
+        };
+        let required = Rent::get()?.minimum_balance(new_len);
+        if required > share_mint.lamports() {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.admin.to_account_info(),
+                        to: share_mint.clone(),
+                    },
+                ),
+                required - share_mint.lamports(),
+            )?;
+        }
+
+        let state_key = ctx.accounts.vault_state.key();
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        for (field, value) in [
+            (Field::Name, updated.name),
+            (Field::Symbol, updated.symbol),
+            (Field::Uri, updated.uri),
+        ] {
+            token_extensions::token_metadata_update_field(
+                CpiContext::new_with_signer(
+                    ctx.accounts.share_token_program.to_account_info(),
+                    token_extensions::TokenMetadataUpdateField {
+                        program_id: ctx.accounts.share_token_program.to_account_info(),
+                        metadata: share_mint.clone(),
+                        update_authority: ctx.accounts.vault_authority.to_account_info(),
+                    },
+                    &[vault_authority_seeds],
+                ),
+                field,
+                value,
+            )?;
+        }
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: switch epoch (batched) settlement on / off
+    // ─────────────────────────────────────────
+    pub fn set_epoch_mode(
+        ctx: Context<AdminAction>,
+        enabled: bool,
+        epoch_duration: i64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.vault_state;
+        if enabled {
+            require!(epoch_duration > 0, VaultError::InvalidEpochDuration);
+            if !state.epoch_mode {
+                state.epoch_started_at = Clock::get()?.unix_timestamp;
+            }
+            state.epoch_duration = epoch_duration;
+        } else {
+            // buckets must be settled before falling back to immediate math
+            require!(
+                state.pending_deposit_assets == 0 && state.pending_redeem_shares == 0,
+                VaultError::PendingEpochRequests
+            );
+        }
+        state.epoch_mode = enabled;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: minimum holding period & early-exit penalty
+    // ─────────────────────────────────────────
+    pub fn set_lockup(
+        ctx: Context<AdminAction>,
+        lockup_period: i64,
+        early_exit_penalty_bps: u16,
+    ) -> Result<()> {
+        require!(lockup_period >= 0, VaultError::InvalidLockup);
+        require!(
+            early_exit_penalty_bps as u64 <= MAX_BPS,
+            VaultError::InvalidBps
+        );
+        let state = &mut ctx.accounts.vault_state;
+        // the hook is what keeps locked shares from leaving by transfer
+        require!(
+            lockup_period == 0
+                || state.non_transferable
+                || state.transfer_hook_program == SHARE_HOOK_PROGRAM_ID,
+            VaultError::LockupNeedsTransferHook
+        );
+        state.lockup_period = lockup_period;
+        state.early_exit_penalty_bps = early_exit_penalty_bps;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: redemption gate (max outflow per window)
+    // ─────────────────────────────────────────
+    pub fn set_redemption_gate(
+        ctx: Context<AdminAction>,
+        gate_bps: u16,
+        gate_window: i64,
+        gate_max_hits: u8,
+    ) -> Result<()> {
+        require!(gate_bps as u64 <= MAX_BPS, VaultError::InvalidBps);
+        require!(
+            gate_bps == 0 || gate_window > 0,
+            VaultError::InvalidGateWindow
+        );
+        let state = &mut ctx.accounts.vault_state;
+        state.gate_bps = gate_bps;
+        state.gate_window = gate_window;
+        state.gate_max_hits = gate_max_hits;
+        // force a fresh window (and cap) on the next withdrawal
+        state.gate_window_start = 0;
+        state.gate_window_cap = 0;
+        state.gate_window_outflow = 0;
+        state.gate_window_hits = 0;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // QUEUED WITHDRAWAL ➜ pay out once the gate has room
+    // ─────────────────────────────────────────
+    /// Pays as much of the queue as fits in the current gate window; the rest
+    /// stays queued for later windows and the account closes once it is empty.
+    pub fn process_queued_withdrawal(ctx: Context<ProcessQueuedWithdrawal>) -> Result<()> {
+        require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+
+        let state = &mut ctx.accounts.vault_state;
+        let queued_shares = ctx.accounts.queued_withdrawal.shares;
+        let now = Clock::get()?.unix_timestamp;
+
+        // shares worth at most the room left in the window, so the payout
+        // (rounded down again) never exceeds it
+        let room = state.gate_room(now)?;
+        let shares = if state.convert_to_assets(queued_shares)? <= room {
+            queued_shares
+        } else {
+            state.convert_to_shares(room)?.min(queued_shares)
+        };
+        require!(shares > 0, VaultError::RedemptionGateExceeded);
+        let asset_amount = state.convert_to_assets(shares)?;
+        require!(asset_amount > 0, VaultError::RoundingError);
+        state.record_outflow(asset_amount)?;
+
+        let state_key = state.key();
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.vault_asset_account.to_account_info(),
+            to: ctx.accounts.owner_asset_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            ),
+            asset_amount,
+        )?;
+
+        state.total_asset = state
+            .total_asset
+            .checked_sub(asset_amount)
+            .ok_or(VaultError::Overflow)?;
+        state.total_shares = state
+            .total_shares
+            .checked_sub(shares)
+            .ok_or(VaultError::Overflow)?;
+        state.queued_redeem_shares = state
+            .queued_redeem_shares
+            .checked_sub(shares)
+            .ok_or(VaultError::Overflow)?;
+        PriceHistory::snapshot(ctx.accounts.price_history.as_mut(), state, now)?;
+        SharePriceFeed::publish(ctx.accounts.share_price_feed.as_mut(), state)?;
+        if let Some(user_position) = ctx.accounts.user_position.as_mut() {
+            user_position.record_withdrawal(shares, asset_amount)?;
+        }
+
+        emit!(WithdrawEvent {
+            sender: ctx.accounts.owner.key(),
+            owner: ctx.accounts.owner.key(),
+            receiver: ctx.accounts.owner_asset_account.owner,
+            shares_burned: shares,
+            asset_amount,
+            early_exit_penalty: 0,
+            timestamp: now,
+        });
+
+        let queued = &mut ctx.accounts.queued_withdrawal;
+        queued.shares = queued_shares - shares;
+        if queued.shares == 0 {
+            queued.close(ctx.accounts.owner.to_account_info())?;
+        }
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // EPOCH MODE: queue Asset A for the next settlement
+    // ─────────────────────────────────────────
+    pub fn request_deposit(ctx: Context<RequestDeposit>, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        require!(
+            ctx.accounts.vault_state.epoch_mode,
+            VaultError::EpochModeDisabled
+        );
+
+        let state = &mut ctx.accounts.vault_state;
+        let request = &mut ctx.accounts.epoch_request;
+        open_epoch_request(request, state, ctx.accounts.user.key())?;
+
+        // assets sit in the vault account but stay out of total_asset until settled
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_asset_account.to_account_info(),
+            to: ctx.accounts.vault_asset_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        request.deposit_assets = request
+            .deposit_assets
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?;
+        state.pending_deposit_assets = state
+            .pending_deposit_assets
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?;
+        check_deposit_cap(state, ctx.accounts.asset_oracle.as_deref())?;
+
+        emit!(DepositRequestedEvent {
+            user: ctx.accounts.user.key(),
+            epoch: state.current_epoch,
+            asset_amount: amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // EPOCH MODE: queue shares for redemption
+    // ─────────────────────────────────────────
+    pub fn request_redeem<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RequestRedeem<'info>>,
+        shares: u64,
+    ) -> Result<()> {
+        require!(shares > 0, VaultError::InvalidAmount);
+        require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        require!(
+            ctx.accounts.vault_state.epoch_mode,
+            VaultError::EpochModeDisabled
+        );
+
+        let state = &mut ctx.accounts.vault_state;
+        let request = &mut ctx.accounts.epoch_request;
+        open_epoch_request(request, state, ctx.accounts.user.key())?;
+
+        // queued redemptions never pay the early-exit penalty, so locked shares stay put
+        match ctx.accounts.position.as_mut() {
+            Some(position) => {
+                let locked_burned = release_locked_shares(
+                    position,
+                    ctx.accounts.user_share_account.amount,
+                    shares,
+                    Clock::get()?.unix_timestamp,
+                )?;
+                require!(locked_burned == 0, VaultError::SharesLocked);
+            }
+            None => require!(state.lockup_period == 0, VaultError::PositionRequired),
+        }
+
+        // shares leave the user now; total_shares only drops at settlement
+        let cpi_accs = token_interface::Burn {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            from: ctx.accounts.user_share_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        token_interface::burn(
+            CpiContext::new(ctx.accounts.share_token_program.to_account_info(), cpi_accs),
+            shares,
+        )?;
+
+        let old_balance = ctx.accounts.user_share_account.amount;
+        sync_reward_checkpoints(
+            ctx.remaining_accounts,
+            state,
+            ctx.accounts.user_share_account.owner,
+            old_balance,
+            old_balance.saturating_sub(shares),
+        )?;
+
+        request.redeem_shares = request
+            .redeem_shares
+            .checked_add(shares)
+            .ok_or(VaultError::Overflow)?;
+        state.pending_redeem_shares = state
+            .pending_redeem_shares
+            .checked_add(shares)
+            .ok_or(VaultError::Overflow)?;
+
+        emit!(RedeemRequestedEvent {
+            user: ctx.accounts.user.key(),
+            epoch: state.current_epoch,
+            shares,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // EPOCH MODE: permissionless crank, one price per bucket
+    // ─────────────────────────────────────────
+    pub fn settle_epoch(ctx: Context<SettleEpoch>) -> Result<()> {
+        require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        require!(
+            ctx.accounts.vault_state.epoch_mode,
+            VaultError::EpochModeDisabled
+        );
+
+        let state = &mut ctx.accounts.vault_state;
+        let now = Clock::get()?.unix_timestamp;
+        let epoch_end = state
+            .epoch_started_at
+            .checked_add(state.epoch_duration)
+            .ok_or(VaultError::Overflow)?;
+        require!(now >= epoch_end, VaultError::EpochNotEnded);
+
+        // ╭──────────────────────────────────╮
+        // │  Price both buckets off the      │
+        // │  same pre-settlement totals      │
+        // ╰──────────────────────────────────╯
+        let deposit_assets = state.pending_deposit_assets;
+        let redeem_shares = state.pending_redeem_shares;
+        let shares_minted = state.convert_to_shares(deposit_assets)?;
+        let assets_redeemed = if redeem_shares == 0 {
+            0
+        } else {
+            state.convert_to_assets(redeem_shares)?
+        };
+
+        let settlement = &mut ctx.accounts.epoch_settlement;
+        settlement.vault = state.key();
+        settlement.epoch = state.current_epoch;
+        settlement.deposit_assets = deposit_assets;
+        settlement.shares_minted = shares_minted;
+        settlement.redeem_shares = redeem_shares;
+        settlement.assets_redeemed = assets_redeemed;
+        settlement.settled_at = now;
+
+        // ╭──────────────────────────────────╮
+        // │  Update state                    │
+        // ╰──────────────────────────────────╯
+        state.total_asset = state
+            .total_asset
+            .checked_add(deposit_assets)
+            .and_then(|v| v.checked_sub(assets_redeemed))
+            .ok_or(VaultError::Overflow)?;
+        state.total_shares = state
+            .total_shares
+            .checked_add(shares_minted)
+            .and_then(|v| v.checked_sub(redeem_shares))
+            .ok_or(VaultError::Overflow)?;
+        state.pending_deposit_assets = 0;
+        state.pending_redeem_shares = 0;
+        state.current_epoch = state
+            .current_epoch
+            .checked_add(1)
+            .ok_or(VaultError::Overflow)?;
+        state.epoch_started_at = now;
+        PriceHistory::snapshot(ctx.accounts.price_history.as_mut(), state, now)?;
+        SharePriceFeed::publish(ctx.accounts.share_price_feed.as_mut(), state)?;
+
+        emit!(EpochSettledEvent {
+            vault: settlement.vault,
+            epoch: settlement.epoch,
+            deposit_assets,
+            shares_minted,
+            redeem_shares,
+            assets_redeemed,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // EPOCH MODE: collect shares / assets of a settled epoch
+    // ─────────────────────────────────────────
+    pub fn claim_epoch<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimEpoch<'info>>) -> Result<()> {
+        require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+
+        let request = &mut ctx.accounts.epoch_request;
+        let settlement = &ctx.accounts.epoch_settlement;
+        require!(
+            request.deposit_assets > 0 || request.redeem_shares > 0,
+            VaultError::NothingToClaim
+        );
+
+        // pro-rata slice of the bucket, rounded down in the vault's favour
+        let shares = if request.deposit_assets == 0 {
+            0
+        } else {
+            mul_div(
+                request.deposit_assets,
+                settlement.shares_minted,
+                settlement.deposit_assets,
+            )?
+        };
+        let asset_amount = if request.redeem_shares == 0 {
+            0
+        } else {
+            mul_div(
+                request.redeem_shares,
+                settlement.assets_redeemed,
+                settlement.redeem_shares,
+            )?
+        };
+
+        // the redemption gate only limits the payout: the part of a redemption
+        // that does not fit in the window stays in the request for a later
+        // claim, while new shares still mint
+        let now = Clock::get()?.unix_timestamp;
+        let state = &mut ctx.accounts.vault_state;
+        let room = state.gate_room(now)?;
+        let (redeemed_shares, asset_amount) = if asset_amount <= room {
+            (request.redeem_shares, asset_amount)
+        } else {
+            let part = mul_div(room, settlement.redeem_shares, settlement.assets_redeemed)?
+                .min(request.redeem_shares);
+            let paid = mul_div(part, settlement.assets_redeemed, settlement.redeem_shares)?;
+            (part, paid)
+        };
+        require!(
+            shares > 0 || redeemed_shares > 0 || request.redeem_shares == 0,
+            VaultError::RedemptionGateExceeded
+        );
+        state.record_outflow(asset_amount)?;
+
+        let state_key = ctx.accounts.vault_state.key();
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds];
+
+        if shares > 0 {
+            let cpi_accounts = token_interface::MintTo {
+                mint: ctx.accounts.share_mint.to_account_info(),
+                to: ctx.accounts.user_share_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            token_interface::mint_to(
+                CpiContext::new_with_signer(
+                    ctx.accounts.share_token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                ),
+                shares,
+            )?;
+
+            if ctx.accounts.vault_state.lockup_period > 0 {
+                let position = ctx
+                    .accounts
+                    .position
+                    .as_mut()
+                    .ok_or(VaultError::PositionRequired)?;
+                lock_new_shares(
+                    position,
+                    &ctx.accounts.vault_state,
+                    ctx.accounts.user.key(),
+                    shares,
+                    true,
+                    now,
+                )?;
+            }
+
+            let old_balance = ctx.accounts.user_share_account.amount;
+            sync_reward_checkpoints(
+                ctx.remaining_accounts,
+                &ctx.accounts.vault_state,
+                ctx.accounts.user.key(),
+                old_balance,
+                old_balance
+                    .checked_add(shares)
+                    .ok_or(VaultError::Overflow)?,
+            )?;
+        }
+
+        // ╭──────────────────────────────────╮
+        // │  Cost basis (optional)           │
+        // ╰──────────────────────────────────╯
+        if let Some(user_position) = ctx.accounts.user_position.as_mut() {
+            user_position.vault = state_key;
+            user_position.owner = ctx.accounts.user.key();
+            user_position.bump = ctx.bumps.user_position.unwrap_or(user_position.bump);
+            if shares > 0 {
+                user_position.record_deposit(request.deposit_assets, shares)?;
+            }
+            if redeemed_shares > 0 {
+                user_position.record_withdrawal(redeemed_shares, asset_amount)?;
+            }
+        }
+
+        if asset_amount > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.vault_asset_account.to_account_info(),
+                to: ctx.accounts.user_asset_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                ),
+                asset_amount,
+            )?;
+        }
+
+        emit!(EpochClaimEvent {
+            user: ctx.accounts.user.key(),
+            epoch: request.epoch,
+            shares_minted: shares,
+            asset_amount,
+            timestamp: now,
+        });
+
+        request.deposit_assets = 0;
+        request.redeem_shares -= redeemed_shares;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: open a reward stream in any SPL mint
+    // ─────────────────────────────────────────
+    /// Checkpoints have to follow every share movement, so the shares must
+    /// either be soulbound or carry the transfer hook, which must then be told
+    /// about the new pool (`share_hook::add_reward_pool`) before shares move.
+    pub fn create_reward_pool(ctx: Context<CreateRewardPool>, reward_rate: u64) -> Result<()> {
+        let state = &mut ctx.accounts.vault_state;
+        require!(
+            state.non_transferable || state.transfer_hook_program == SHARE_HOOK_PROGRAM_ID,
+            VaultError::RewardsNeedTransferHook
+        );
+        require!(
+            (state.reward_pool_count as usize) < MAX_REWARD_POOLS,
+            VaultError::TooManyRewardPools
+        );
+        state.reward_pool_count += 1;
+
+        let pool = &mut ctx.accounts.reward_pool;
+        pool.vault = state.key();
+        pool.reward_mint = ctx.accounts.reward_mint.key();
+        pool.reward_vault = ctx.accounts.reward_vault.key();
+        pool.reward_rate = reward_rate;
+        pool.acc_reward_per_share = 0;
+        pool.last_update_ts = Clock::get()?.unix_timestamp;
+        pool.total_funded = 0;
+        pool.total_accrued = 0;
+        pool.total_claimed = 0;
+        pool.staked_shares = 0;
+        pool.unclaimable = 0;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: top up a reward pool
+    // ─────────────────────────────────────────
+    pub fn fund_reward_pool(ctx: Context<FundRewardPool>, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+
+        let pool = &mut ctx.accounts.reward_pool;
+        let now = Clock::get()?.unix_timestamp;
+        pool.accrue(now)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            to: ctx.accounts.reward_vault.to_account_info(),
+            authority: ctx.accounts.admin.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        pool.total_funded = pool
+            .total_funded
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?;
+
+        emit!(RewardPoolFundedEvent {
+            reward_pool: pool.key(),
+            amount,
+            reward_rate: pool.reward_rate,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: change a pool's distribution rate
+    // ─────────────────────────────────────────
+    pub fn set_reward_rate(ctx: Context<SetRewardRate>, reward_rate: u64) -> Result<()> {
+        let pool = &mut ctx.accounts.reward_pool;
+        // settle everything earned at the old rate first
+        pool.accrue(Clock::get()?.unix_timestamp)?;
+        pool.reward_rate = reward_rate;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // REWARDS: opt in / re-sync a checkpoint to the current share balance
+    // ─────────────────────────────────────────
+    pub fn update_reward_checkpoint(ctx: Context<UpdateRewardCheckpoint>) -> Result<()> {
+        let pool = &mut ctx.accounts.reward_pool;
+        pool.accrue(Clock::get()?.unix_timestamp)?;
+
+        let checkpoint = &mut ctx.accounts.reward_checkpoint;
+        checkpoint.pool = pool.key();
+        checkpoint.owner = ctx.accounts.user.key();
+        let balance = ctx.accounts.user_share_account.amount;
+        checkpoint.sync(pool, balance, balance)
+    }
+
+    // ─────────────────────────────────────────
+    // TRANSFER HOOK: move reward checkpoints and cost basis with a transfer
+    // ─────────────────────────────────────────
+    /// Called by the share mint's transfer hook on every transfer. The hook
+    /// signs with its `hook_authority` PDA, so `amount` can be trusted to
+    /// rebuild both pre-transfer balances. Remaining accounts are one
+    /// `[reward_pool, source_checkpoint, destination_checkpoint]` triple per
+    /// pool of the vault, so a transfer fails until the hook tracks every
+    /// pool; checkpoints of holders who never opted in are skipped. The cost
+    /// of the moved shares follows them like a withdrawal takes it out of
+    /// the sender's `UserPosition`; a receiver without one drops it.
+    pub fn sync_transfer_checkpoints<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SyncTransferCheckpoints<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let state = &ctx.accounts.vault_state;
+        require!(
+            state.transfer_hook_program == SHARE_HOOK_PROGRAM_ID,
+            VaultError::InvalidHookAuthority
+        );
+        let (hook_authority, _) =
+            Pubkey::find_program_address(&[b"hook_authority"], &SHARE_HOOK_PROGRAM_ID);
+        require_keys_eq!(
+            ctx.accounts.hook_authority.key(),
+            hook_authority,
+            VaultError::InvalidHookAuthority
+        );
+
+        let source = &ctx.accounts.source_share_account;
+        let destination = &ctx.accounts.destination_share_account;
+        let source_before = source
+            .amount
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?;
+        let destination_before = destination
+            .amount
+            .checked_sub(amount)
+            .ok_or(VaultError::Overflow)?;
+
+        let triples = ctx.remaining_accounts.chunks_exact(3);
+        require!(
+            triples.remainder().is_empty() && triples.len() == state.reward_pool_count as usize,
+            VaultError::InvalidRewardAccounts
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let mut seen = Vec::with_capacity(triples.len());
+        for triple in triples {
+            let mut pool = load_reward_pool(&triple[0], state, &mut seen)?;
+            pool.accrue(now)?;
+            sync_opted_in_checkpoint(
+                &triple[1],
+                &mut pool,
+                source.owner,
+                source_before,
+                source.amount,
+            )?;
+            sync_opted_in_checkpoint(
+                &triple[2],
+                &mut pool,
+                destination.owner,
+                destination_before,
+                destination.amount,
+            )?;
+            pool.exit(&crate::ID)?;
+        }
+
+        // ╭──────────────────────────────────╮
+        // │  Cost basis                      │
+        // ╰──────────────────────────────────╯
+        let from = &ctx.accounts.source_user_position;
+        let to = &ctx.accounts.destination_user_position;
+        if from.key() != to.key() {
+            if let Some(mut sender) = load_user_position(from)? {
+                let (shares, cost) = sender.release_cost(amount)?;
+                store_user_position(from, &sender)?;
+                if let Some(mut receiver) = load_user_position(to)? {
+                    receiver.add_cost(shares, cost)?;
+                    store_user_position(to, &receiver)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // REWARDS: pay out everything accrued so far
+    // ─────────────────────────────────────────
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        let pool = &mut ctx.accounts.reward_pool;
+        let now = Clock::get()?.unix_timestamp;
+        pool.accrue(now)?;
+
+        let checkpoint = &mut ctx.accounts.reward_checkpoint;
+        let balance = ctx.accounts.user_share_account.amount;
+        checkpoint.sync(pool, balance, balance)?;
+
+        let amount = checkpoint.pending;
+        require!(amount > 0, VaultError::NothingToClaim);
+
+        let state_key = ctx.accounts.vault_state.key();
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.user_reward_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        checkpoint.pending = 0;
+        pool.total_claimed = pool
+            .total_claimed
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?;
+
+        emit!(RewardsClaimedEvent {
+            user: ctx.accounts.user.key(),
+            reward_mint: pool.reward_mint,
+            amount,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: recover rewards no checkpoint can claim
+    // ─────────────────────────────────────────
+    pub fn sweep_unclaimable_rewards(ctx: Context<SweepUnclaimableRewards>) -> Result<()> {
+        let pool = &mut ctx.accounts.reward_pool;
+        let now = Clock::get()?.unix_timestamp;
+        pool.accrue(now)?;
+
+        let amount = pool.unclaimable;
+        require!(amount > 0, VaultError::NothingToClaim);
+
+        let state_key = ctx.accounts.vault_state.key();
+        let vault_authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.destination.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            ),
+            amount,
+        )?;
+        pool.unclaimable = 0;
+
+        emit!(RewardsSweptEvent {
+            reward_pool: pool.key(),
+            amount,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: swap program & keeper bounty (`compound`, recurring deposits)
+    // ─────────────────────────────────────────
+    /// `max_slippage_bps` bounds how far below the oracle quote a `compound`
+    /// swap may fill.
+    pub fn set_compound_config(
+        ctx: Context<AdminAction>,
+        swap_program: Pubkey,
+        keeper_bounty_bps: u16,
+        max_slippage_bps: u16,
+    ) -> Result<()> {
+        require!(
+            keeper_bounty_bps as u64 <= MAX_BPS && max_slippage_bps as u64 <= MAX_BPS,
+            VaultError::InvalidBps
+        );
+        let state = &mut ctx.accounts.vault_state;
+        state.swap_program = swap_program;
+        state.keeper_bounty_bps = keeper_bounty_bps;
+        state.compound_max_slippage_bps = max_slippage_bps;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: account collecting one reward mint for compounding
+    // ─────────────────────────────────────────
+    /// `reward_oracle` prices the reward mint in USD (`PriceUpdateV2` layout)
+    /// and sets the floor of every swap out of this harvest account.
+    pub fn create_harvest_account(ctx: Context<CreateHarvestAccount>) -> Result<()> {
+        let config = &mut ctx.accounts.harvest_config;
+        config.vault = ctx.accounts.vault_state.key();
+        config.reward_mint = ctx.accounts.reward_mint.key();
+        config.reward_oracle = ctx.accounts.reward_oracle.key();
+        config.reward_decimals = ctx.accounts.reward_mint.decimals;
+        config.bump = ctx.bumps.harvest_config;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // KEEPER: swap harvested rewards into Asset A
+    // ─────────────────────────────────────────
+    /// The swap must return at least the oracle value of the harvest in
+    /// Asset A, less `compound_max_slippage_bps`; the keeper has no say in
+    /// the floor.
+    pub fn compound<'info>(ctx: Context<'_, '_, 'info, 'info, Compound<'info>>) -> Result<()> {
+        require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+
+        let amount_in = ctx.accounts.harvest_account.amount;
+        require!(amount_in > 0, VaultError::NothingToCompound);
+        let balance_before = ctx.accounts.vault_asset_account.amount;
+
+        // ╭──────────────────────────────────╮
+        // │  Slippage floor from the oracles │
+        // ╰──────────────────────────────────╯
+        let state = &ctx.accounts.vault_state;
+        let now = Clock::get()?.unix_timestamp;
+        let asset_price = OraclePrice::load(&ctx.accounts.asset_oracle, state, now)?;
+        let reward_price = OraclePrice::parse(
+            &ctx.accounts.reward_oracle,
+            state.oracle_max_age,
+            state.oracle_max_conf_bps,
+            now,
+        )?;
+        let quote = asset_price.asset_amount(
+            reward_price.usd_value(amount_in, ctx.accounts.harvest_config.reward_decimals)?,
+            state.asset_decimals,
+        )?;
+        let min_amount_out = mul_div(
+            quote,
+            MAX_BPS - state.compound_max_slippage_bps as u64,
+            MAX_BPS,
+        )?;
+
+        // ╭──────────────────────────────────╮
+        // │  CPI into the swap program       │
+        // ╰──────────────────────────────────╯
+        // layout: [authority, source, destination, token_program, ...pool accounts]
+        // the swap authority only controls harvest accounts, never vault assets
+        let mut metas = vec![
+            AccountMeta::new_readonly(ctx.accounts.swap_authority.key(), true),
+            AccountMeta::new(ctx.accounts.harvest_account.key(), false),
+            AccountMeta::new(ctx.accounts.vault_asset_account.key(), false),
+            AccountMeta::new_readonly(ctx.accounts.token_program.key(), false),
+        ];
+        let mut infos = vec![
+            ctx.accounts.swap_authority.to_account_info(),
+            ctx.accounts.harvest_account.to_account_info(),
+            ctx.accounts.vault_asset_account.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+        ];
+        for acc in ctx.remaining_accounts {
+            metas.push(if acc.is_writable {
+                AccountMeta::new(acc.key(), acc.is_signer)
+            } else {
+                AccountMeta::new_readonly(acc.key(), acc.is_signer)
+            });
+            infos.push(acc.clone());
+        }
+        infos.push(ctx.accounts.swap_program.to_account_info());
+
+        let mut data = SWAP_IX_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_amount_out.to_le_bytes());
+
+        let state_key = ctx.accounts.vault_state.key();
+        let swap_authority_seeds: &[&[u8]] = &[
+            b"swap_authority",
+            state_key.as_ref(),
+            &[ctx.bumps.swap_authority],
+        ];
+        invoke_signed(
+            &Instruction {
+                program_id: ctx.accounts.swap_program.key(),
+                accounts: metas,
+                data,
+            },
+            &infos,
+            &[swap_authority_seeds],
+        )?;
+
+        // ╭──────────────────────────────────╮
+        // │  Measure proceeds ourselves      │
+        // ╰──────────────────────────────────╯
+        ctx.accounts.vault_asset_account.reload()?;
+        let proceeds = ctx
+            .accounts
+            .vault_asset_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(VaultError::Overflow)?;
+        require!(
+            proceeds > 0 && proceeds >= min_amount_out,
+            VaultError::SlippageExceeded
+        );
+
+        // ╭──────────────────────────────────╮
+        // │  Pay the keeper's bounty         │
+        // ╰──────────────────────────────────╯
+        let state = &mut ctx.accounts.vault_state;
+        let bounty = mul_div(proceeds, state.keeper_bounty_bps as u64, MAX_BPS)?;
+        if bounty > 0 {
+            let vault_authority_seeds: &[&[u8]] = &[
+                b"vault_authority",
+                state_key.as_ref(),
+                &[ctx.bumps.vault_authority],
+            ];
+            let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds];
+
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.vault_asset_account.to_account_info(),
+                to: ctx.accounts.keeper_asset_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                ),
+                bounty,
+            )?;
+        }
+
+        // the yield goes to the holders, minus the protocol's cut
+        let net = proceeds - bounty;
+        let protocol_fee = ctx.accounts.protocol_config.protocol_cut(net)?;
+        state.total_asset = state
+            .total_asset
+            .checked_add(net - protocol_fee)
+            .ok_or(VaultError::Overflow)?;
+        state.protocol_fees_accrued = state
+            .protocol_fees_accrued
+            .checked_add(protocol_fee)
+            .ok_or(VaultError::Overflow)?;
+        PriceHistory::snapshot(ctx.accounts.price_history.as_mut(), state, now)?;
+        SharePriceFeed::publish(ctx.accounts.share_price_feed.as_mut(), state)?;
+
+        emit!(CompoundEvent {
+            vault: state_key,
+            reward_mint: ctx.accounts.harvest_account.mint,
+            amount_in,
+            proceeds,
+            keeper_bounty: bounty,
+            keeper: ctx.accounts.keeper.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // BASKET VAULT  ➜ several mints, oracle-priced shares
+    // ─────────────────────────────────────────
+    /// Opens an empty basket whose shares have `USD_DECIMALS` decimals and
+    /// start at one USD; constituents are added with `add_basket_asset`. A
+    /// deposit may lift its constituent to at most its target weight plus
+    /// `max_weight_deviation_bps`, a withdrawal may lower it to at least its
+    /// target minus that. Baskets have no single asset mint, so they stay out
+    /// of the vault registry.
+    pub fn initialize_basket(
+        ctx: Context<InitializeBasket>,
+        basket_id: u64,
+        oracle_max_age: i64,
+        oracle_max_conf_bps: u16,
+        max_weight_deviation_bps: u16,
+    ) -> Result<()> {
+        require!(oracle_max_age > 0, VaultError::InvalidOracle);
+        require!(
+            oracle_max_conf_bps as u64 <= MAX_BPS && max_weight_deviation_bps as u64 <= MAX_BPS,
+            VaultError::InvalidBps
+        );
+        let basket = &mut ctx.accounts.basket;
+        basket.admin = ctx.accounts.admin.key();
+        basket.basket_id = basket_id;
+        basket.share_mint = ctx.accounts.share_mint.key();
+        basket.total_shares = 0;
+        basket.oracle_max_age = oracle_max_age;
+        basket.oracle_max_conf_bps = oracle_max_conf_bps;
+        basket.max_weight_deviation_bps = max_weight_deviation_bps;
+        basket.bump = ctx.bumps.basket;
+        basket.authority_bump = ctx.bumps.basket_authority;
+        basket.paused = false;
+        basket.asset_count = 0;
+        basket.assets = [BasketAsset::default(); MAX_BASKET_ASSETS];
+        Ok(())
+    }
+
+    /// Stops deposits, withdrawals and in-kind redemptions of one basket.
+    pub fn set_basket_pause(ctx: Context<BasketAdmin>, pause: bool) -> Result<()> {
+        ctx.accounts.basket.paused = pause;
+        Ok(())
+    }
+
+    /// Adds a constituent priced by `oracle` while the basket has no shares;
+    /// target weights must add up to 100 % before the first deposit.
+    pub fn add_basket_asset(ctx: Context<AddBasketAsset>, target_weight_bps: u16) -> Result<()> {
+        let basket = &mut ctx.accounts.basket;
+        let mint = ctx.accounts.asset_mint.key();
+        require!(basket.total_shares == 0, VaultError::BasketNotEmpty);
+        require!(
+            (basket.asset_count as usize) < MAX_BASKET_ASSETS,
+            VaultError::BasketFull
+        );
+        require!(
+            ctx.accounts
+                .protocol_config
+                .allowed_asset_mints
+                .contains(&mint),
+            VaultError::AssetMintNotAllowed
+        );
+        require!(
+            basket.assets().iter().all(|asset| asset.mint != mint),
+            VaultError::InvalidBasketAsset
+        );
+        require!(
+            target_weight_bps > 0
+                && basket.total_weight_bps() + target_weight_bps as u64 <= MAX_BPS,
+            VaultError::InvalidBasketWeights
+        );
+
+        let index = basket.asset_count as usize;
+        basket.assets[index] = BasketAsset {
+            mint,
+            vault_account: ctx.accounts.basket_asset_account.key(),
+            oracle: ctx.accounts.oracle.key(),
+            decimals: ctx.accounts.asset_mint.decimals,
+            target_weight_bps,
+            balance: 0,
+        };
+        basket.asset_count += 1;
+        Ok(())
+    }
+
+    /// Deposits `amount` of the constituent held by `basket_asset_account`,
+    /// minting shares for its USD value against the basket's NAV.
+    /// Remaining accounts: every constituent's oracle, in basket order.
+    pub fn basket_deposit<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BasketDeposit<'info>>,
+        amount: u64,
+        min_shares: u64,
+    ) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let basket = &mut ctx.accounts.basket;
+        require!(!basket.paused, VaultError::VaultPaused);
+        require!(
+            basket.total_weight_bps() == MAX_BPS,
+            VaultError::InvalidBasketWeights
+        );
+        let index = basket.index_of(&ctx.accounts.basket_asset_account.key())?;
+        let prices = basket.load_prices(ctx.remaining_accounts, now)?;
+        let values = basket.values_usd(&prices)?;
+        let nav = values
+            .iter()
+            .try_fold(0u64, |sum, value| sum.checked_add(*value))
+            .ok_or(VaultError::Overflow)?;
+        let value = prices[index].usd_value(amount, basket.assets[index].decimals)?;
+
+        // the first deposit is necessarily all one constituent
+        if nav > 0 {
+            let weight = mul_div(
+                values[index]
+                    .checked_add(value)
+                    .ok_or(VaultError::Overflow)?,
+                MAX_BPS,
+                nav.checked_add(value).ok_or(VaultError::Overflow)?,
+            )?;
+            require!(
+                weight
+                    <= basket.assets[index].target_weight_bps as u64
+                        + basket.max_weight_deviation_bps as u64,
+                VaultError::BasketWeightExceeded
+            );
+        }
+
+        let shares = if basket.total_shares == 0 || nav == 0 {
+            value
+        } else {
+            mul_div(value, basket.total_shares, nav)?
+        };
+        require!(
+            shares > 0 && shares >= min_shares,
+            VaultError::SlippageExceeded
+        );
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_asset_account.to_account_info(),
+                    to: ctx.accounts.basket_asset_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let basket_key = basket.key();
+        let basket_authority_seeds: &[&[u8]] = &[
+            b"basket_authority",
+            basket_key.as_ref(),
+            &[basket.authority_bump],
+        ];
+        token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_interface::MintTo {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    to: ctx.accounts.receiver_share_account.to_account_info(),
+                    authority: ctx.accounts.basket_authority.to_account_info(),
+                },
+                &[basket_authority_seeds],
+            ),
+            shares,
+        )?;
+
+        let asset = &mut basket.assets[index];
+        asset.balance = asset
+            .balance
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?;
+        basket.total_shares = basket
+            .total_shares
+            .checked_add(shares)
+            .ok_or(VaultError::Overflow)?;
+
+        emit!(BasketDepositEvent {
+            basket: basket_key,
+            user: ctx.accounts.user.key(),
+            mint: basket.assets[index].mint,
+            asset_amount: amount,
+            value_usd: value,
+            shares_minted: shares,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    /// Burns `shares` for their USD value paid in the single constituent
+    /// held by `basket_asset_account`. Remaining accounts: every
+    /// constituent's oracle, in basket order.
+    pub fn basket_withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BasketWithdraw<'info>>,
+        shares: u64,
+        min_amount_out: u64,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let basket = &mut ctx.accounts.basket;
+        require!(!basket.paused, VaultError::VaultPaused);
+        require!(
+            shares > 0 && shares <= basket.total_shares,
+            VaultError::InvalidShares
+        );
+        let index = basket.index_of(&ctx.accounts.basket_asset_account.key())?;
+        let prices = basket.load_prices(ctx.remaining_accounts, now)?;
+        let values = basket.values_usd(&prices)?;
+        let nav = values
+            .iter()
+            .try_fold(0u64, |sum, value| sum.checked_add(*value))
+            .ok_or(VaultError::Overflow)?;
+        let value = mul_div(shares, nav, basket.total_shares)?;
+        let amount = prices[index].asset_amount(value, basket.assets[index].decimals)?;
+        require!(amount >= min_amount_out, VaultError::SlippageExceeded);
+        require!(
+            amount <= basket.assets[index].balance,
+            VaultError::InsufficientBasketLiquidity
+        );
+
+        // the last shares out may empty the basket whatever the weights
+        if shares < basket.total_shares && nav > value {
+            let weight = mul_div(values[index].saturating_sub(value), MAX_BPS, nav - value)?;
+            require!(
+                weight
+                    >= (basket.assets[index].target_weight_bps as u64)
+                        .saturating_sub(basket.max_weight_deviation_bps as u64),
+                VaultError::BasketWeightExceeded
+            );
+        }
+
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_interface::Burn {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    from: ctx.accounts.user_share_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+
+        let basket_key = basket.key();
+        let basket_authority_seeds: &[&[u8]] = &[
+            b"basket_authority",
+            basket_key.as_ref(),
+            &[basket.authority_bump],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.basket_asset_account.to_account_info(),
+                    to: ctx.accounts.receiver_asset_account.to_account_info(),
+                    authority: ctx.accounts.basket_authority.to_account_info(),
+                },
+                &[basket_authority_seeds],
+            ),
+            amount,
+        )?;
+
+        let asset = &mut basket.assets[index];
+        asset.balance = asset
+            .balance
+            .checked_sub(amount)
+            .ok_or(VaultError::Overflow)?;
+        basket.total_shares = basket
+            .total_shares
+            .checked_sub(shares)
+            .ok_or(VaultError::Overflow)?;
+
+        emit!(BasketWithdrawEvent {
+            basket: basket_key,
+            user: ctx.accounts.user.key(),
+            mint: basket.assets[index].mint,
+            shares_burned: shares,
+            value_usd: value,
+            asset_amount: amount,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    /// Burns `shares` for the same fraction of every constituent; needs no
+    /// oracle. Remaining accounts: `(basket_asset_account, receiver token
+    /// account)` per constituent, in basket order.
+    pub fn basket_redeem_in_kind<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BasketRedeemInKind<'info>>,
+        shares: u64,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.protocol_config.global_pause,
+            VaultError::ProtocolPaused
+        );
+        let basket = &mut ctx.accounts.basket;
+        require!(!basket.paused, VaultError::VaultPaused);
+        require!(
+            shares > 0 && shares <= basket.total_shares,
+            VaultError::InvalidShares
+        );
+        require!(
+            ctx.remaining_accounts.len() == 2 * basket.asset_count as usize,
+            VaultError::InvalidBasketAsset
+        );
+
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_interface::Burn {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    from: ctx.accounts.user_share_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+
+        let basket_key = basket.key();
+        let basket_authority_seeds: &[&[u8]] = &[
+            b"basket_authority",
+            basket_key.as_ref(),
+            &[basket.authority_bump],
+        ];
+        let total_shares = basket.total_shares;
+        let mut amounts = Vec::with_capacity(basket.asset_count as usize);
+        for (asset, pair) in basket
+            .assets
+            .iter_mut()
+            .zip(ctx.remaining_accounts.chunks(2))
+        {
+            require_keys_eq!(
+                pair[0].key(),
+                asset.vault_account,
+                VaultError::InvalidBasketAsset
+            );
+            let amount = mul_div(asset.balance, shares, total_shares)?;
+            if amount > 0 {
+                // the token program rejects a receiver of another mint
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: pair[0].clone(),
+                            to: pair[1].clone(),
+                            authority: ctx.accounts.basket_authority.to_account_info(),
+                        },
+                        &[basket_authority_seeds],
+                    ),
+                    amount,
+                )?;
+                asset.balance = asset
+                    .balance
+                    .checked_sub(amount)
+                    .ok_or(VaultError::Overflow)?;
+            }
+            amounts.push(amount);
+        }
+        basket.total_shares = basket
+            .total_shares
+            .checked_sub(shares)
+            .ok_or(VaultError::Overflow)?;
+
+        emit!(BasketRedeemedInKindEvent {
+            basket: basket_key,
+            user: ctx.accounts.user.key(),
+            shares_burned: shares,
+            asset_amounts: amounts,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// NAV, value of one whole share and current weights of the basket.
+    /// Remaining accounts: every constituent's oracle, in basket order.
+    /// Read-only; use `.view()` in TS.
+    pub fn get_basket_nav<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ViewBasket<'info>>,
+    ) -> Result<BasketNavView> {
+        let basket = &ctx.accounts.basket;
+        let prices = basket.load_prices(ctx.remaining_accounts, Clock::get()?.unix_timestamp)?;
+        let values = basket.values_usd(&prices)?;
+        let nav = values
+            .iter()
+            .try_fold(0u64, |sum, value| sum.checked_add(*value));
+        let nav = nav.ok_or(VaultError::Overflow)?;
+        let one_share = 10u64.pow(USD_DECIMALS as u32);
+        Ok(BasketNavView {
+            nav_usd: nav,
+            share_price_usd: if basket.total_shares == 0 {
+                one_share
+            } else {
+                mul_div(one_share, nav, basket.total_shares)?
+            },
+            weights_bps: values
+                .iter()
+                .map(|value| match nav {
+                    0 => Ok(0),
+                    _ => mul_div(*value, MAX_BPS, nav).map(|bps| bps as u16),
+                })
+                .collect::<Result<_>>()?,
+            target_weights_bps: basket
+                .assets()
+                .iter()
+                .map(|asset| asset.target_weight_bps)
+                .collect(),
+        })
+    }
+}
+
+// ╭────────────────────────────────────────────
+// │               HELPERS                      │
+// ╰────────────────────────────────────────────
+pub const MAX_BPS: u64 = 10_000;
+/// The `share-hook` program, the only transfer hook a share mint may name:
+/// lockups and reward checkpoints rely on it. Its crate depends on this one,
+/// so the id is repeated here.
+pub const SHARE_HOOK_PROGRAM_ID: Pubkey = pubkey!("5d9592JRYbS325CZt2oN6Z1ryg3atUNpTvMkcYavXyrD");
+/// Anchor sighash of `global:swap`, the entrypoint `compound` calls on the
+/// configured swap program.
+pub const SWAP_IX_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+/// Length caps for share-token metadata (Metaplex limits, so both ecosystems agree).
+pub const MAX_NAME_LEN: usize = 32;
+pub const MAX_SYMBOL_LEN: usize = 10;
+pub const MAX_URI_LEN: usize = 200;
+/// Capacity of `ProtocolConfig::allowed_asset_mints`.
+pub const MAX_ALLOWED_MINTS: usize = 64;
+/// Reward pools one vault can run (each costs three accounts per share transfer).
+pub const MAX_REWARD_POOLS: usize = 4;
+/// Fixed-point scale of `RewardPool::acc_reward_per_share`.
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+/// Fixed-point scale of per-share asset prices (assets per share * 1e9).
+pub const PRICE_PRECISION: u64 = 1_000_000_000;
+/// Decimals of USD amounts (`deposit_cap_usd`, `get_nav_usd`).
+pub const USD_DECIMALS: u8 = 6;
+/// Constituents a `BasketState` can hold.
+pub const MAX_BASKET_ASSETS: usize = 4;
+/// Snapshots kept by a vault's `PriceHistory` ring buffer.
+pub const PRICE_HISTORY_LEN: usize = 48;
+
+/// Shared body of the deposit instructions: pulls in `amount` (from the
+/// signer, through the vault authority's delegation when `delegated`, or as
+/// lamports), mints the shares and returns how many.
+fn deposit_assets<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Deposit<'info>>,
+    amount: u64,
+    delegated: bool,
+) -> Result<u64> {
+    // ╭──────────────────────────────────╮
+    // │  Safety & vault-state sanity     │
+    // ╰──────────────────────────────────╯
+    require!(amount > 0, VaultError::InvalidAmount);
+    require!(!ctx.accounts.vault_state.paused, VaultError::VaultPaused);
+    require!(
+        !ctx.accounts.protocol_config.global_pause,
+        VaultError::ProtocolPaused
+    );
+    require!(
+        !ctx.accounts.vault_state.epoch_mode,
+        VaultError::EpochModeActive
+    );
+
+    let state = &mut ctx.accounts.vault_state;
+    let state_key = state.key(); // own the Pubkey for the rest of the function
+    let vault_authority_seeds: &[&[u8]] = &[
+        b"vault_authority",
+        state_key.as_ref(),
+        &[ctx.bumps.vault_authority],
+    ];
+    let signer_seeds: &[&[&[u8]]] = &[vault_authority_seeds]; // &[&[u8]] → &[&[&[u8]]]
+
+    // ╭──────────────────────────────────╮
+    // │  Transfer Asset A into vault     │
+    // ╰──────────────────────────────────╯
+    match ctx.accounts.user_asset_account.as_ref() {
+        // intent / recurring: the owner delegated to the vault authority
+        Some(user_asset_account) if delegated => {
+            let cpi_accounts = Transfer {
+                from: user_asset_account.to_account_info(),
+                to: ctx.accounts.vault_asset_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                ),
+                amount,
+            )?;
+        }
+        Some(user_asset_account) => {
+            let cpi_accounts = Transfer {
+                from: user_asset_account.to_account_info(),
+                to: ctx.accounts.vault_asset_account.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                amount,
+            )?;
+        }
+        // native vault paid in lamports: wrap them straight into the vault
+        None => {
+            require_keys_eq!(
+                state.asset_mint,
+                token::spl_token::native_mint::ID,
+                VaultError::NotNativeVault
+            );
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.user.to_account_info(),
+                        to: ctx.accounts.vault_asset_account.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+            token::sync_native(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::SyncNative {
+                    account: ctx.accounts.vault_asset_account.to_account_info(),
+                },
+            ))?;
+        }
+    }
+
+    // ╭──────────────────────────────────╮
+    // │  Calculate shares to mint        │
+    // ╰──────────────────────────────────╯
+    // first deposit ⇒ 1:1, afterwards amount * total_shares / total_asset
+    let shares_to_mint = state.convert_to_shares(amount)?;
+    require!(shares_to_mint > 0, VaultError::RoundingError);
+
+    // ╭──────────────────────────────────╮
+    // │  Mint shares to the receiver     │
+    // ╰──────────────────────────────────╯
+    let cpi_accounts = token_interface::MintTo {
+        mint: ctx.accounts.share_mint.to_account_info(),
+        to: ctx.accounts.receiver_share_account.to_account_info(),
+        authority: ctx.accounts.vault_authority.to_account_info(),
+    };
+    token_interface::mint_to(
+        CpiContext::new_with_signer(
+            ctx.accounts.share_token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        ),
+        shares_to_mint,
+    )?;
+
+    // ╭──────────────────────────────────╮
+    // │  Reward checkpoints              │
+    // ╰──────────────────────────────────╯
+    let receiver = ctx.accounts.receiver.key();
+    let old_balance = ctx.accounts.receiver_share_account.amount;
+    sync_reward_checkpoints(
+        ctx.remaining_accounts,
+        state,
+        receiver,
+        old_balance,
+        old_balance
+            .checked_add(shares_to_mint)
+            .ok_or(VaultError::Overflow)?,
+    )?;
+
+    // ╭──────────────────────────────────╮
+    // │  Update state                    │
+    // ╰──────────────────────────────────╯
+    state.total_asset = state
+        .total_asset
+        .checked_add(amount)
+        .ok_or(VaultError::Overflow)?;
+    state.total_shares = state
+        .total_shares
+        .checked_add(shares_to_mint)
+        .ok_or(VaultError::Overflow)?;
+    check_deposit_cap(state, ctx.accounts.asset_oracle.as_deref())?;
+
+    // ╭──────────────────────────────────╮
+    // │  Lock the new shares             │
+    // ╰──────────────────────────────────╯
+    let funder = ctx
+        .accounts
+        .user_asset_account
+        .as_ref()
+        .map_or(ctx.accounts.user.key(), |account| account.owner);
+    if state.lockup_period > 0 {
+        let position = ctx
+            .accounts
+            .position
+            .as_mut()
+            .ok_or(VaultError::PositionRequired)?;
+        // only the receiver's own deposits may push their unlock time out
+        lock_new_shares(
+            position,
+            state,
+            receiver,
+            shares_to_mint,
+            funder == receiver,
+            Clock::get()?.unix_timestamp,
+        )?;
+    }
+
+    PriceHistory::snapshot(
+        ctx.accounts.price_history.as_mut(),
+        state,
+        Clock::get()?.unix_timestamp,
+    )?;
+    SharePriceFeed::publish(ctx.accounts.share_price_feed.as_mut(), state)?;
+
+    // ╭──────────────────────────────────╮
+    // │  Cost basis (optional)           │
+    // ╰──────────────────────────────────╯
+    // shares paid for by someone else carry no cost basis, so a third party
+    // cannot move the receiver's average
+    if let Some(user_position) = ctx.accounts.user_position.as_mut() {
+        user_position.vault = state_key;
+        user_position.owner = receiver;
+        user_position.bump = ctx.bumps.user_position.unwrap_or(user_position.bump);
+        if funder == receiver {
+            user_position.record_deposit(amount, shares_to_mint)?;
+        }
+    }
+
+    // ╭──────────────────────────────────╮
+    // │  Emit event                      │
+    // ╰──────────────────────────────────╯
+    emit!(DepositEvent {
+        sender: ctx.accounts.user.key(),
+        owner: funder,
+        receiver,
+        asset_amount: amount,
+        shares_minted: shares_to_mint,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    msg!("DepositEvent finished!");
+    Ok(shares_to_mint)
+}
+
+/// `init_if_needed` for a PDA passed as a remaining account: creates `info`
+/// at `seeds` (without the bump), paid by `payer`, unless it exists. Returns
+/// the account and its bump.
+fn init_pda_if_needed<'info, T>(
+    info: &'info AccountInfo<'info>,
+    seeds: &[&[u8]],
+    space: usize,
+    payer: &AccountInfo<'info>,
+    system: &AccountInfo<'info>,
+) -> Result<(Account<'info, T>, u8)>
+where
+    T: AccountSerialize + AccountDeserialize + Discriminator + Owner + Clone,
+{
+    let (expected, bump) = Pubkey::find_program_address(seeds, &crate::ID);
+    require_keys_eq!(info.key(), expected, VaultError::InvalidBatchAccounts);
+    if info.owner == &system_program::ID {
+        let bump_seed = [bump];
+        let mut pda_seeds = seeds.to_vec();
+        pda_seeds.push(&bump_seed);
+        let signer_seeds: &[&[&[u8]]] = &[&pda_seeds];
+        let rent = Rent::get()?.minimum_balance(space);
+        let lamports = info.lamports();
+        if lamports == 0 {
+            system_program::create_account(
+                CpiContext::new_with_signer(
+                    system.clone(),
+                    system_program::CreateAccount {
+                        from: payer.clone(),
+                        to: info.clone(),
+                    },
+                    signer_seeds,
+                ),
+                rent,
+                space as u64,
+                &crate::ID,
+            )?;
+        } else {
+            // someone sent lamports to the address first: top up and take it over
+            if rent > lamports {
+                system_program::transfer(
+                    CpiContext::new(
+                        system.clone(),
+                        system_program::Transfer {
+                            from: payer.clone(),
+                            to: info.clone(),
+                        },
+                    ),
+                    rent - lamports,
+                )?;
+            }
+            system_program::allocate(
+                CpiContext::new_with_signer(
+                    system.clone(),
+                    system_program::Allocate {
+                        account_to_allocate: info.clone(),
+                    },
+                    signer_seeds,
+                ),
+                space as u64,
+            )?;
+            system_program::assign(
+                CpiContext::new_with_signer(
+                    system.clone(),
+                    system_program::Assign {
+                        account_to_assign: info.clone(),
+                    },
+                    signer_seeds,
+                ),
+                &crate::ID,
+            )?;
+        }
+        info.try_borrow_mut_data()?[..8].copy_from_slice(T::DISCRIMINATOR);
+    }
+    Ok((Account::try_from(info)?, bump))
+}
+
+/// Checks that the instruction right before this one is an ed25519 program
+/// instruction verifying `signer`'s signature over exactly `message`, with
+/// all of its data inline.
+fn verify_ed25519_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions)? as usize;
+    require!(current > 0, VaultError::InvalidIntent);
+    let ix = load_instruction_at_checked(current - 1, instructions)?;
+    require_keys_eq!(
+        ix.program_id,
+        ed25519_program::ID,
+        VaultError::InvalidIntent
+    );
+
+    // [count, padding] then one Ed25519SignatureOffsets record
+    let data = &ix.data;
+    require!(data.len() >= 16 && data[0] == 1, VaultError::InvalidIntent);
+    let read = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    require!(
+        read(4) == u16::MAX && read(8) == u16::MAX && read(14) == u16::MAX,
+        VaultError::InvalidIntent
+    );
+    let (key_at, msg_at, msg_len) = (read(6) as usize, read(10) as usize, read(12) as usize);
+    require!(
+        data.get(key_at..key_at + 32) == Some(signer.as_ref())
+            && data.get(msg_at..msg_at + msg_len) == Some(message),
+        VaultError::InvalidIntent
+    );
+    Ok(())
+}
+
+/// Token-2022 metadata for a share mint, with the vault authority as update authority.
+fn share_metadata(
+    mint: Pubkey,
+    update_authority: Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Result<TokenMetadata> {
+    require!(
+        name.len() <= MAX_NAME_LEN && symbol.len() <= MAX_SYMBOL_LEN && uri.len() <= MAX_URI_LEN,
+        VaultError::InvalidMetadata
+    );
+    Ok(TokenMetadata {
+        update_authority: OptionalNonZeroPubkey::try_from(Some(update_authority))?,
+        mint,
+        name,
+        symbol,
+        uri,
+        additional_metadata: Vec::new(),
+    })
+}
+
+/// Assets per share scaled by `PRICE_PRECISION` (1:1 while the vault is empty).
+fn share_price(total_asset: u64, total_shares: u64) -> Result<u64> {
+    if total_shares == 0 || total_asset == 0 {
+        return Ok(PRICE_PRECISION);
+    }
+    mul_div(total_asset, PRICE_PRECISION, total_shares)
+}
+
+/// Enforces `deposit_cap_usd` against the vault's assets, pending epoch
+/// deposits included. Call after the deposit is booked.
+fn check_deposit_cap(state: &VaultState, oracle: Option<&AccountInfo>) -> Result<()> {
+    if state.deposit_cap_usd == 0 {
+        return Ok(());
+    }
+    let oracle = oracle.ok_or(VaultError::OracleRequired)?;
+    let price = OraclePrice::load(oracle, state, Clock::get()?.unix_timestamp)?;
+    let assets = state
+        .total_asset
+        .checked_add(state.pending_deposit_assets)
+        .ok_or(VaultError::Overflow)?;
+    require!(
+        price.usd_value(assets, state.asset_decimals)? <= state.deposit_cap_usd,
+        VaultError::DepositCapExceeded
+    );
+    Ok(())
+}
+
+/// a * b / c in u128; callers handle empty buckets, a zero `c` is an error
+fn mul_div(a: u64, b: u64, c: u64) -> Result<u64> {
+    require!(c > 0, VaultError::DivisionByZero);
+    let v = (a as u128)
+        .checked_mul(b as u128)
+        .ok_or(VaultError::Overflow)?
+        / c as u128;
+    u64::try_from(v).map_err(|_| VaultError::Overflow.into())
+}
+
+/// Walks one `(reward_pool, reward_checkpoint)` pair per reward pool of the
+/// vault, passed as remaining accounts, and moves the owner's checkpoint in
+/// each from `old_balance` to `new_balance`. Holders who never opted in still
+/// pass their (empty) checkpoint PDA.
+fn sync_reward_checkpoints<'info>(
+    remaining_accounts: &'info [AccountInfo<'info>],
+    state: &Account<'info, VaultState>,
+    owner: Pubkey,
+    old_balance: u64,
+    new_balance: u64,
+) -> Result<()> {
+    let pairs = remaining_accounts.chunks_exact(2);
+    require!(
+        pairs.remainder().is_empty() && pairs.len() == state.reward_pool_count as usize,
+        VaultError::InvalidRewardAccounts
+    );
+    let now = Clock::get()?.unix_timestamp;
+    let mut seen = Vec::with_capacity(pairs.len());
+    for pair in pairs {
+        let mut pool = load_reward_pool(&pair[0], state, &mut seen)?;
+        pool.accrue(now)?;
+        sync_opted_in_checkpoint(&pair[1], &mut pool, owner, old_balance, new_balance)?;
+        pool.exit(&crate::ID)?;
+    }
+    Ok(())
+}
+
+/// A holder's `UserPosition`, or `None` if they never opened one.
+fn load_user_position(info: &AccountInfo) -> Result<Option<UserPosition>> {
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(UserPosition::try_deserialize(
+        &mut &info.try_borrow_data()?[..],
+    )?))
+}
+
+fn store_user_position(info: &AccountInfo, position: &UserPosition) -> Result<()> {
+    let mut data = info.try_borrow_mut_data()?;
+    position.try_serialize(&mut &mut data[..])
+}
+
+/// Loads one of `state`'s reward pools, refusing any already in `seen`, so
+/// `reward_pool_count` entries cover every pool exactly once.
+fn load_reward_pool<'info>(
+    info: &'info AccountInfo<'info>,
+    state: &Account<'info, VaultState>,
+    seen: &mut Vec<Pubkey>,
+) -> Result<Account<'info, RewardPool>> {
+    let pool = Account::<RewardPool>::try_from(info)?;
+    require_keys_eq!(pool.vault, state.key(), VaultError::InvalidRewardAccounts);
+    require!(
+        !seen.contains(&pool.key()),
+        VaultError::InvalidRewardAccounts
+    );
+    seen.push(pool.key());
+    Ok(pool)
+}
+
+/// Syncs `info`, which must be `owner`'s checkpoint PDA in `pool`; an account
+/// that was never initialised means the holder has not opted in.
+fn sync_opted_in_checkpoint<'info>(
+    info: &'info AccountInfo<'info>,
+    pool: &mut Account<'info, RewardPool>,
+    owner: Pubkey,
+    live_balance: u64,
+    new_balance: u64,
+) -> Result<()> {
+    let (expected, _) = Pubkey::find_program_address(
+        &[b"reward_checkpoint", pool.key().as_ref(), owner.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(info.key(), expected, VaultError::InvalidRewardAccounts);
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(());
+    }
+    let mut checkpoint = Account::<RewardCheckpoint>::try_from(info)?;
+    require_keys_eq!(
+        checkpoint.pool,
+        pool.key(),
+        VaultError::InvalidRewardAccounts
+    );
+    require_keys_eq!(checkpoint.owner, owner, VaultError::InvalidRewardAccounts);
+    checkpoint.sync(pool, live_balance, new_balance)?;
+    checkpoint.exit(&crate::ID)
+}
+
+/// Counts a withdrawal queued by the gate and trips the circuit breaker once
+/// `gate_max_hits` is reached inside one window. Only queued hits count: a
+/// withdrawal without a queue account fails and reverts with it, and a held
+/// back epoch claim costs nothing to repeat.
+fn record_gate_hit(state: &mut Account<VaultState>, now: i64) {
+    state.gate_window_hits = state.gate_window_hits.saturating_add(1);
+    if state.gate_max_hits > 0 && state.gate_window_hits >= state.gate_max_hits {
+        state.paused = true;
+        emit!(RedemptionGateAlertEvent {
+            vault: state.key(),
+            window_outflow: state.gate_window_outflow,
+            window_cap: state.gate_window_cap,
+            hits: state.gate_window_hits,
+            timestamp: now,
+        });
+    }
+}
+
+/// Adds freshly minted shares to the holder's lock. With `extend` (the
+/// holder's own deposit) the unlock time of everything still locked moves out
+/// to `now + lockup_period`; shares deposited by someone else join a running
+/// lock as is, so a third party cannot keep a holder locked in.
+fn lock_new_shares(
+    position: &mut Position,
+    state: &Account<VaultState>,
+    owner: Pubkey,
+    shares: u64,
+    extend: bool,
+    now: i64,
+) -> Result<()> {
+    let expired = now >= position.unlock_ts;
+    if expired {
+        position.locked_shares = 0;
+    }
+    position.vault = state.key();
+    position.owner = owner;
+    position.locked_shares = position
+        .locked_shares
+        .checked_add(shares)
+        .ok_or(VaultError::Overflow)?;
+    if extend || expired {
+        position.unlock_ts = now
+            .checked_add(state.lockup_period)
+            .ok_or(VaultError::Overflow)?;
+    }
+    Ok(())
+}
+
+/// Takes `shares` out of a holder's `balance`, spending unlocked shares first.
+/// Returns how many of the burned shares were still locked.
+fn release_locked_shares(
+    position: &mut Position,
+    balance: u64,
+    shares: u64,
+    now: i64,
+) -> Result<u64> {
+    if now >= position.unlock_ts {
+        position.locked_shares = 0;
+        return Ok(0);
+    }
+    let locked = position.locked_shares.min(balance);
+    let free = balance - locked;
+    let locked_burned = shares.saturating_sub(free).min(locked);
+    position.locked_shares = locked - locked_burned;
+    Ok(locked_burned)
+}
+
+/// (Re)binds an epoch request to the current epoch; a request from a
+/// settled epoch has to be claimed before the user can queue again.
+fn open_epoch_request(
+    request: &mut EpochRequest,
+    state: &Account<VaultState>,
+    user: Pubkey,
+) -> Result<()> {
+    if request.deposit_assets == 0 && request.redeem_shares == 0 {
+        request.vault = state.key();
+        request.user = user;
+        request.epoch = state.current_epoch;
+    }
+    require!(
+        request.epoch == state.current_epoch,
+        VaultError::UnclaimedEpochRequest
+    );
+    Ok(())
+}
+
+// ╭────────────────────────────────────────────
+// │              ACCOUNT STATE                 │
+// ╰────────────────────────────────────────────
+#[account]
+pub struct VaultState {
+    /*  8 */ pub asset_mint: Pubkey,
+    /* 40 */ pub share_mint: Pubkey,
+    /* 72 */ pub vault_authority: Pubkey,
+    /*104 */ pub vault_asset_account: Pubkey,
+    /*136 */ pub admin: Pubkey,
+    /*168 */ pub total_asset: u64,
+    /*176 */ pub total_shares: u64,
+    /*184 */ pub paused: bool,
+    /*185 */ _padding: [u8; 7],
+    /*192 */ pub epoch_mode: bool,
+    /*193 */ pub epoch_duration: i64,
+    /*201 */ pub current_epoch: u64,
+    /*209 */ pub epoch_started_at: i64,
+    /*217 */ pub pending_deposit_assets: u64,
+    /*225 */ pub pending_redeem_shares: u64,
+    /*233 */ pub lockup_period: i64,
+    /*241 */ pub early_exit_penalty_bps: u16,
+    /*243 */ pub gate_bps: u16,
+    /*245 */ pub gate_window: i64,
+    /*253 */ pub gate_max_hits: u8,
+    /*254 */ pub gate_window_start: i64,
+    /*262 */ pub gate_window_cap: u64,
+    /*270 */ pub gate_window_outflow: u64,
+    /*278 */ pub gate_window_hits: u8,
+    /*279 */ pub queued_redeem_shares: u64,
+    /*287 */ pub swap_program: Pubkey,
+    /*319 */ pub keeper_bounty_bps: u16,
+    /*321 */ pub vault_id: u64,
+    /*329 */ pub bump: u8,
+    /*330 */ pub protocol_fees_accrued: u64,
+    /*338 */ pub non_transferable: bool,
+    /*339 */ pub transfer_hook_program: Pubkey,
+    /*371 */ pub flash_fee_bps: u16,
+    /*373 */ pub flash_paused: bool,
+    /*374 */ pub flash_loan_amount: u64, // open loan within the current tx
+    /*382 */ pub oracle: Pubkey, // Asset A / USD price account, default = none
+    /*414 */ pub oracle_max_age: i64,
+    /*422 */ pub oracle_max_conf_bps: u16,
+    /*424 */ pub deposit_cap_usd: u64, // 0 = no cap
+    /*432 */ pub asset_decimals: u8,
+    /*433 */ pub reward_pool_count: u8,
+    /*434 */ pub compound_max_slippage_bps: u16, // vs. the oracle quote
+    /*436 */ pub has_share_price_feed: bool, // state changes must then pass it
+    /*437 */ pub has_price_history: bool, // likewise
+}
+const _VAULT_STATE_SIZE: usize = 8
+    + 32 * 5
+    + 8 * 2
+    + 1
+    + 7
+    + 1
+    + 8 * 6
+    + 2
+    + 2
+    + 8
+    + 1
+    + 8 * 3
+    + 1
+    + 8
+    + 32
+    + 2
+    + 8
+    + 1
+    + 8
+    + 1
+    + 32
+    + 2
+    + 1
+    + 8
+    + 32
+    + 8
+    + 2
+    + 8
+    + 1
+    + 1
+    + 2
+    + 1
+    + 1; // = 438
+
+impl VaultState {
+    /// Shares worth `assets` at the current price (1:1 while the vault is empty).
+    pub fn convert_to_shares(&self, assets: u64) -> Result<u64> {
+        if self.total_shares == 0 || self.total_asset == 0 {
+            return Ok(assets);
+        }
+        // assets * total_shares / total_asset   (all in u128 for safety)
+        mul_div(assets, self.total_shares, self.total_asset)
+    }
+
+    /// Asset A owed for burning `shares` at the current price.
+    pub fn convert_to_assets(&self, shares: u64) -> Result<u64> {
+        require!(self.total_shares > 0, VaultError::InvalidShares);
+        // shares * total_asset / total_shares
+        mul_div(shares, self.total_asset, self.total_shares)
+    }
+
+    /// Rolls the gate window when it has expired and reports whether another
+    /// `amount` of Asset A may leave in the current one.
+    pub fn gate_allows(&mut self, amount: u64, now: i64) -> Result<bool> {
+        Ok(amount <= self.gate_room(now)?)
+    }
+
+    /// Rolls the gate window when it has expired and returns how much Asset A
+    /// may still leave in the current one (`u64::MAX` without a gate).
+    pub fn gate_room(&mut self, now: i64) -> Result<u64> {
+        if self.gate_bps == 0 {
+            return Ok(u64::MAX);
+        }
+        let window_end = self
+            .gate_window_start
+            .checked_add(self.gate_window)
+            .ok_or(VaultError::Overflow)?;
+        if now >= window_end {
+            // the cap is fixed when the window opens so outflows can't shrink it
+            self.gate_window_start = now;
+            self.gate_window_cap = mul_div(self.total_asset, self.gate_bps as u64, MAX_BPS)?;
+            self.gate_window_outflow = 0;
+            self.gate_window_hits = 0;
+        }
+        Ok(self
+            .gate_window_cap
+            .saturating_sub(self.gate_window_outflow))
+    }
+
+    pub fn record_outflow(&mut self, amount: u64) -> Result<()> {
+        self.gate_window_outflow = self
+            .gate_window_outflow
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?;
+        Ok(())
+    }
+}
+
+/// Per-holder lockup: shares minted within the last `lockup_period`.
+#[account]
+pub struct Position {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub locked_shares: u64,
+    pub unlock_ts: i64,
+}
+const _POSITION_SIZE: usize = 8 + 32 * 2 + 8 + 8;
+
+/// Optional per-holder accounting kept by deposits and withdrawals. Only
+/// shares minted to the holder carry a cost, plus the sender's cost of
+/// shares moved through the share hook; other shares are redeemed at zero
+/// cost.
+#[account]
+pub struct UserPosition {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub total_deposited: u64,
+    pub total_withdrawn: u64,
+    pub shares: u64,     // shares the cost basis covers
+    pub cost_basis: u64, // assets paid for `shares`
+    pub realized_pnl: i64,
+    pub bump: u8,
+}
+const _USER_POSITION_SIZE: usize = 8 + 32 * 2 + 8 * 5 + 1;
+
+impl UserPosition {
+    pub fn record_deposit(&mut self, assets: u64, shares: u64) -> Result<()> {
+        self.total_deposited = self
+            .total_deposited
+            .checked_add(assets)
+            .ok_or(VaultError::Overflow)?;
+        self.add_cost(shares, assets)
+    }
+
+    /// Releases the average cost of `shares` and realizes `proceeds` against it.
+    pub fn record_withdrawal(&mut self, shares: u64, proceeds: u64) -> Result<()> {
+        let (_, cost) = self.release_cost(shares)?;
+        self.total_withdrawn = self
+            .total_withdrawn
+            .checked_add(proceeds)
+            .ok_or(VaultError::Overflow)?;
+        let pnl =
+            i64::try_from(proceeds as i128 - cost as i128).map_err(|_| VaultError::Overflow)?;
+        self.realized_pnl = self
+            .realized_pnl
+            .checked_add(pnl)
+            .ok_or(VaultError::Overflow)?;
+        Ok(())
+    }
+
+    /// Takes the covered part of `shares` out of the basis at its average
+    /// cost; returns the covered shares and their cost.
+    pub fn release_cost(&mut self, shares: u64) -> Result<(u64, u64)> {
+        let covered = shares.min(self.shares);
+        let cost = if covered == 0 {
+            0
+        } else {
+            mul_div(self.cost_basis, covered, self.shares)?
+        };
+        self.shares -= covered;
+        self.cost_basis -= cost;
+        Ok((covered, cost))
+    }
+
+    pub fn add_cost(&mut self, shares: u64, cost: u64) -> Result<()> {
+        self.shares = self
+            .shares
+            .checked_add(shares)
+            .ok_or(VaultError::Overflow)?;
+        self.cost_basis = self
+            .cost_basis
+            .checked_add(cost)
+            .ok_or(VaultError::Overflow)?;
+        Ok(())
+    }
+
+    pub fn view(&self) -> UserPositionView {
+        UserPositionView {
+            total_deposited: self.total_deposited,
+            total_withdrawn: self.total_withdrawn,
+            shares: self.shares,
+            cost_basis: self.cost_basis,
+            avg_cost_per_share: if self.shares == 0 {
+                0
+            } else {
+                mul_div(self.cost_basis, PRICE_PRECISION, self.shares).unwrap_or(u64::MAX)
+            },
+            realized_pnl: self.realized_pnl,
+        }
+    }
+}
+
+/// Returned by `get_user_position`; `avg_cost_per_share` is scaled by
+/// `PRICE_PRECISION`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct UserPositionView {
+    pub total_deposited: u64,
+    pub total_withdrawn: u64,
+    pub shares: u64,
+    pub cost_basis: u64,
+    pub avg_cost_per_share: u64,
+    pub realized_pnl: i64,
+}
+
+/// One basket constituent; `balance` is what deposits and withdrawals have
+/// booked, so donations to `vault_account` do not move the NAV.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct BasketAsset {
+    pub mint: Pubkey,
+    pub vault_account: Pubkey,
+    pub oracle: Pubkey, // USD price, `PriceUpdateV2` layout
+    pub decimals: u8,
+    pub target_weight_bps: u16,
+    pub balance: u64,
+}
+
+/// Multi-asset vault: shares are priced by the USD value of all
+/// constituents and can be entered or left through any of them.
+#[account]
+pub struct BasketState {
+    pub admin: Pubkey,
+    pub basket_id: u64,
+    pub share_mint: Pubkey,
+    pub total_shares: u64,
+    pub oracle_max_age: i64,
+    pub oracle_max_conf_bps: u16,
+    /// how far above its target weight a deposit may lift a constituent
+    pub max_weight_deviation_bps: u16,
+    pub bump: u8,
+    pub authority_bump: u8,
+    pub paused: bool,
+    pub asset_count: u8,
+    pub assets: [BasketAsset; MAX_BASKET_ASSETS],
+}
+const _BASKET_STATE_SIZE: usize =
+    8 + 32 + 8 + 32 + 8 + 8 + 2 + 2 + 4 + (32 * 3 + 1 + 2 + 8) * MAX_BASKET_ASSETS;
+
+impl BasketState {
+    pub fn assets(&self) -> &[BasketAsset] {
+        &self.assets[..self.asset_count as usize]
+    }
+
+    pub fn total_weight_bps(&self) -> u64 {
+        self.assets()
+            .iter()
+            .map(|asset| asset.target_weight_bps as u64)
+            .sum()
+    }
+
+    pub fn index_of(&self, vault_account: &Pubkey) -> Result<usize> {
+        self.assets()
+            .iter()
+            .position(|asset| asset.vault_account == *vault_account)
+            .ok_or(VaultError::InvalidBasketAsset.into())
+    }
+
+    /// Prices every constituent from `oracles`, passed in basket order.
+    pub fn load_prices(&self, oracles: &[AccountInfo], now: i64) -> Result<Vec<OraclePrice>> {
+        require!(
+            oracles.len() >= self.assets().len(),
+            VaultError::OracleRequired
+        );
+        self.assets()
+            .iter()
+            .zip(oracles)
+            .map(|(asset, oracle)| {
+                require_keys_eq!(oracle.key(), asset.oracle, VaultError::InvalidOracle);
+                OraclePrice::parse(oracle, self.oracle_max_age, self.oracle_max_conf_bps, now)
+            })
+            .collect()
+    }
+
+    pub fn values_usd(&self, prices: &[OraclePrice]) -> Result<Vec<u64>> {
+        self.assets()
+            .iter()
+            .zip(prices)
+            .map(|(asset, price)| price.usd_value(asset.balance, asset.decimals))
+            .collect()
+    }
+
+    pub fn nav_usd(&self, prices: &[OraclePrice]) -> Result<u64> {
+        self.values_usd(prices)?
+            .into_iter()
+            .try_fold(0u64, |sum, value| sum.checked_add(value))
+            .ok_or(VaultError::Overflow.into())
+    }
+}
+
+/// Returned by `get_basket_nav`; USD amounts have `USD_DECIMALS` decimals
+/// and weights follow basket order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BasketNavView {
+    pub nav_usd: u64,
+    pub share_price_usd: u64,
+    pub weights_bps: Vec<u16>,
+    pub target_weights_bps: Vec<u16>,
+}
+
+/// Anchor discriminator of Pyth's `PriceUpdateV2` account.
+pub const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+/// The fields of a `PriceUpdateV2` account the vault prices with.
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    /// `parse` with the vault's oracle account and limits.
+    pub fn load(oracle: &AccountInfo, state: &VaultState, now: i64) -> Result<Self> {
+        require_keys_eq!(oracle.key(), state.oracle, VaultError::InvalidOracle);
+        Self::parse(oracle, state.oracle_max_age, state.oracle_max_conf_bps, now)
+    }
+
+    /// Reads `oracle` (`discriminator, write_authority, verification_level,
+    /// feed_id, price, conf, exponent, publish_time, ...`) and applies the
+    /// staleness and confidence limits. Only fully verified updates are
+    /// accepted.
+    pub fn parse(oracle: &AccountInfo, max_age: i64, max_conf_bps: u16, now: i64) -> Result<Self> {
+        let data = oracle.try_borrow_data()?;
+        const FULL: u8 = 1;
+        const MESSAGE: usize = 8 + 32 + 1 + 32; // price, after a `Full` level
+        require!(
+            data.len() >= MESSAGE + 28
+                && data[..8] == PRICE_UPDATE_V2_DISCRIMINATOR
+                && data[40] == FULL,
+            VaultError::InvalidOracle
+        );
+        let read = |at: usize| -> [u8; 8] { data[at..at + 8].try_into().unwrap() };
+        let price = Self {
+            price: i64::from_le_bytes(read(MESSAGE)),
+            conf: u64::from_le_bytes(read(MESSAGE + 8)),
+            expo: i32::from_le_bytes(data[MESSAGE + 16..MESSAGE + 20].try_into().unwrap()),
+            publish_time: i64::from_le_bytes(read(MESSAGE + 20)),
+        };
+
+        require!(price.price > 0, VaultError::InvalidOracle);
+        require!(
+            now.saturating_sub(price.publish_time) <= max_age,
+            VaultError::OracleStale
+        );
+        require!(
+            price.conf as u128 * MAX_BPS as u128 <= price.price as u128 * max_conf_bps as u128,
+            VaultError::OracleConfidenceTooWide
+        );
+        Ok(price)
+    }
+
+    /// USD value (`USD_DECIMALS`) of `amount` base units of Asset A.
+    pub fn usd_value(&self, amount: u64, decimals: u8) -> Result<u64> {
+        let scale = USD_DECIMALS as i32 + self.expo - decimals as i32;
+        let factor = 10u128
+            .checked_pow(scale.unsigned_abs())
+            .ok_or(VaultError::Overflow)?;
+        let value = (amount as u128)
+            .checked_mul(self.price as u128)
+            .ok_or(VaultError::Overflow)?;
+        let value = if scale >= 0 {
+            value.checked_mul(factor).ok_or(VaultError::Overflow)?
+        } else {
+            value / factor
+        };
+        u64::try_from(value).map_err(|_| VaultError::Overflow.into())
+    }
+
+    /// Base units of Asset A worth `usd` (`USD_DECIMALS`), rounded down.
+    pub fn asset_amount(&self, usd: u64, decimals: u8) -> Result<u64> {
+        let scale = USD_DECIMALS as i32 + self.expo - decimals as i32;
+        let factor = 10u128
+            .checked_pow(scale.unsigned_abs())
+            .ok_or(VaultError::Overflow)?;
+        let amount = if scale >= 0 {
+            usd as u128
+                / (self.price as u128)
+                    .checked_mul(factor)
+                    .ok_or(VaultError::Overflow)?
+        } else {
+            (usd as u128)
+                .checked_mul(factor)
+                .ok_or(VaultError::Overflow)?
+                / self.price as u128
+        };
+        u64::try_from(amount).map_err(|_| VaultError::Overflow.into())
+    }
+}
+
+/// Returned by `get_nav_usd`; USD amounts have `USD_DECIMALS` decimals.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NavUsdView {
+    pub tvl_usd: u64,
+    pub share_price_usd: u64,
+    pub oracle_price: i64,
+    pub oracle_expo: i32,
+    pub oracle_publish_time: i64,
+}
+
+/// One share price observation.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceSnapshot {
+    pub timestamp: i64,
+    pub total_asset: u64,
+    pub total_shares: u64,
+}
+
+impl PriceSnapshot {
+    /// Assets per share scaled by `PRICE_PRECISION` (1:1 while empty).
+    pub fn price(&self) -> Result<u64> {
+        share_price(self.total_asset, self.total_shares)
+    }
+}
+
+/// Ring buffer of the vault's last `PRICE_HISTORY_LEN` price snapshots,
+/// at least `interval` seconds apart.
+#[account]
+pub struct PriceHistory {
+    pub vault: Pubkey,
+    pub interval: i64,
+    pub head: u16, // slot the next snapshot goes to
+    pub len: u16,
+    pub bump: u8,
+    pub snapshots: [PriceSnapshot; PRICE_HISTORY_LEN],
+}
+const _PRICE_HISTORY_SIZE: usize = 8 + 32 + 8 + 2 + 2 + 1 + (8 * 3) * PRICE_HISTORY_LEN;
+
+impl PriceHistory {
+    /// `i`-th snapshot, oldest first.
+    fn at(&self, i: usize) -> &PriceSnapshot {
+        let oldest = self.head as usize + PRICE_HISTORY_LEN - self.len as usize;
+        &self.snapshots[(oldest + i) % PRICE_HISTORY_LEN]
+    }
+
+    /// Records through `history`, which a vault with a history must pass.
+    pub fn snapshot(
+        history: Option<&mut Box<Account<PriceHistory>>>,
+        state: &VaultState,
+        now: i64,
+    ) -> Result<()> {
+        match history {
+            Some(history) => {
+                history.record(now, state.total_asset, state.total_shares);
+                Ok(())
+            }
+            None => {
+                require!(!state.has_price_history, VaultError::PriceHistoryRequired);
+                Ok(())
+            }
+        }
+    }
+
+    /// Appends a snapshot unless the latest one is younger than `interval`.
+    pub fn record(&mut self, now: i64, total_asset: u64, total_shares: u64) -> bool {
+        if self.len > 0 && now < self.at(self.len as usize - 1).timestamp + self.interval {
+            return false;
+        }
+        self.snapshots[self.head as usize] = PriceSnapshot {
+            timestamp: now,
+            total_asset,
+            total_shares,
+        };
+        self.head = ((self.head as usize + 1) % PRICE_HISTORY_LEN) as u16;
+        self.len = (self.len + 1).min(PRICE_HISTORY_LEN as u16);
+        true
+    }
+
+    /// Each snapshot's price holds until the next one (the latest until
+    /// `now`); the oldest must be at or before the start of the window.
+    pub fn twap(&self, now: i64, window: i64) -> Result<u64> {
+        require!(window > 0, VaultError::InvalidPriceInterval);
+        let start = now - window;
+        require!(
+            self.len > 0 && self.at(0).timestamp <= start,
+            VaultError::InsufficientPriceHistory
+        );
+
+        let mut weighted: u128 = 0;
+        for i in 0..self.len as usize {
+            let snapshot = self.at(i);
+            let until = if i + 1 < self.len as usize {
+                self.at(i + 1).timestamp
+            } else {
+                now
+            };
+            let from = snapshot.timestamp.max(start);
+            if until > from {
+                weighted = weighted
+                    .checked_add(snapshot.price()? as u128 * (until - from) as u128)
+                    .ok_or(VaultError::Overflow)?;
+            }
+        }
+        u64::try_from(weighted / window as u128).map_err(|_| VaultError::Overflow.into())
+    }
+}
+
+/// Oracle-style share price, republished whenever a state change is passed
+/// the feed. The layout is fixed so that other programs can read it by
+/// offset (see the `reza-share-price-feed` crate); append-only.
+#[account]
+pub struct SharePriceFeed {
+    /*  8 */ pub vault: Pubkey,
+    /* 40 */ pub share_mint: Pubkey,
+    /* 72 */ pub price: u64, // assets per share * 10^-expo
+    /* 80 */ pub expo: i32,
+    /* 84 */ pub last_price_move: u64, // |price - previous price|
+    /* 92 */ pub total_asset: u64,
+    /*100 */ pub total_shares: u64,
+    /*108 */ pub last_update_slot: u64,
+    /*116 */ pub last_update_ts: i64,
+    /*124 */ pub max_staleness_slots: u64, // > 0
+    /*132 */ pub bump: u8,
+}
+const _SHARE_PRICE_FEED_SIZE: usize = 8 + 32 * 2 + 8 + 4 + 8 * 6 + 1; // = 133
+
+impl SharePriceFeed {
+    /// `price` is scaled by `PRICE_PRECISION` = 10^9.
+    pub const EXPO: i32 = -9;
+
+    /// Republishes through `feed`, which a vault with a feed must pass.
+    pub fn publish(
+        feed: Option<&mut Box<Account<SharePriceFeed>>>,
+        state: &VaultState,
+    ) -> Result<()> {
+        match feed {
+            Some(feed) => feed.update(state),
+            None => {
+                require!(
+                    !state.has_share_price_feed,
+                    VaultError::SharePriceFeedRequired
+                );
+                Ok(())
+            }
+        }
+    }
+
+    pub fn update(&mut self, state: &VaultState) -> Result<()> {
+        let clock = Clock::get()?;
+        let price = share_price(state.total_asset, state.total_shares)?;
+        self.last_price_move = price.abs_diff(self.price);
+        self.price = price;
+        self.total_asset = state.total_asset;
+        self.total_shares = state.total_shares;
+        self.last_update_slot = clock.slot;
+        self.last_update_ts = clock.unix_timestamp;
+        Ok(())
+    }
+}
+
+/// Protocol-wide singleton: owner, fee split, mint allow-list and vault count.
+#[account]
+pub struct ProtocolConfig {
+    pub owner: Pubkey,
+    pub fee_share_bps: u16,
+    pub allowed_asset_mints: Vec<Pubkey>,
+    pub vault_count: u64,
+    pub bump: u8,
+    pub guardian: Pubkey,
+    pub global_pause: bool,
+}
+const _PROTOCOL_CONFIG_SIZE: usize = 8 + 32 + 2 + 4 + 32 * MAX_ALLOWED_MINTS + 8 + 1 + 32 + 1;
+
+impl ProtocolConfig {
+    /// The protocol's share of `fee` revenue.
+    pub fn protocol_cut(&self, fee: u64) -> Result<u64> {
+        mul_div(fee, self.fee_share_bps as u64, MAX_BPS)
+    }
+}
+
+/// Registry slot `index` ─ enumerate vaults by walking 0..vault_count.
+#[account]
+pub struct VaultRegistryEntry {
+    pub index: u64,
+    pub vault: Pubkey,
+    pub asset_mint: Pubkey,
+    pub vault_id: u64,
+    pub admin: Pubkey,
+}
+const _VAULT_REGISTRY_ENTRY_SIZE: usize = 8 + 8 + 32 * 2 + 8 + 32;
+
+/// A reward stream: `reward_rate` tokens per second shared across opted-in shares.
+#[account]
+pub struct RewardPool {
+    pub vault: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub reward_rate: u64,
+    pub acc_reward_per_share: u128,
+    pub last_update_ts: i64,
+    pub total_funded: u64,
+    pub total_accrued: u64,
+    pub total_claimed: u64,
+    /// sum of `RewardCheckpoint::shares` over every opted-in holder
+    pub staked_shares: u64,
+    /// accrued to shares a checkpoint no longer held, left for `sweep_unclaimable_rewards`
+    pub unclaimable: u64,
+}
+const _REWARD_POOL_SIZE: usize = 8 + 32 * 3 + 8 + 16 + 8 + 8 * 3 + 8 * 2;
+
+impl RewardPool {
+    /// Streams rewards up to `now` into the per-share accumulator of opted-in
+    /// shares. Emission is capped by what has been funded; nothing accrues
+    /// while no checkpoint holds shares.
+    pub fn accrue(&mut self, now: i64) -> Result<()> {
+        if now <= self.last_update_ts {
+            return Ok(());
+        }
+        let staked_shares = self.staked_shares;
+        if staked_shares > 0 && self.reward_rate > 0 {
+            let elapsed = (now - self.last_update_ts) as u64;
+            let available = self.total_funded.saturating_sub(self.total_accrued);
+            let reward = self.reward_rate.saturating_mul(elapsed).min(available);
+            let per_share = (reward as u128)
+                .checked_mul(REWARD_PRECISION)
+                .ok_or(VaultError::Overflow)?
+                / staked_shares as u128;
+            self.acc_reward_per_share = self
+                .acc_reward_per_share
+                .checked_add(per_share)
+                .ok_or(VaultError::Overflow)?;
+            self.total_accrued = self
+                .total_accrued
+                .checked_add(reward)
+                .ok_or(VaultError::Overflow)?;
+        }
+        self.last_update_ts = now;
+        Ok(())
+    }
+}
+
+/// A holder's position in one reward pool.
+#[account]
+pub struct RewardCheckpoint {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    /// share balance recorded at the last sync
+    pub shares: u64,
+    pub acc_snapshot: u128,
+    pub pending: u64,
+}
+const _REWARD_CHECKPOINT_SIZE: usize = 8 + 32 * 2 + 8 + 16 + 8;
+
+impl RewardCheckpoint {
+    /// Books rewards earned since the last sync and records `new_balance`,
+    /// moving the pool's `staked_shares` with it. The pool paid out on the
+    /// recorded shares; if the holder has fewer by now (a balance change that
+    /// was not synced) the difference becomes `unclaimable` instead.
+    pub fn sync(
+        &mut self,
+        pool: &mut RewardPool,
+        live_balance: u64,
+        new_balance: u64,
+    ) -> Result<()> {
+        let delta = pool.acc_reward_per_share - self.acc_snapshot;
+        let accrued = reward_for(self.shares, delta)?;
+        let earned = reward_for(self.shares.min(live_balance), delta)?;
+        self.pending = self
+            .pending
+            .checked_add(earned)
+            .ok_or(VaultError::Overflow)?;
+        pool.unclaimable = pool
+            .unclaimable
+            .checked_add(accrued - earned)
+            .ok_or(VaultError::Overflow)?;
+        pool.staked_shares = pool
+            .staked_shares
+            .checked_sub(self.shares)
+            .and_then(|rest| rest.checked_add(new_balance))
+            .ok_or(VaultError::Overflow)?;
+        self.acc_snapshot = pool.acc_reward_per_share;
+        self.shares = new_balance;
+        Ok(())
+    }
+}
+
+/// Rewards `shares` earned while the accumulator grew by `delta`.
+fn reward_for(shares: u64, delta: u128) -> Result<u64> {
+    let reward = (shares as u128)
+        .checked_mul(delta)
+        .ok_or(VaultError::Overflow)?
+        / REWARD_PRECISION;
+    u64::try_from(reward).map_err(|_| VaultError::Overflow.into())
+}
+
+/// Pricing of one harvest account's reward mint, read by `compound`.
+#[account]
+pub struct HarvestConfig {
+    pub vault: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_oracle: Pubkey,
+    pub reward_decimals: u8,
+    pub bump: u8,
+}
+const _HARVEST_CONFIG_SIZE: usize = 8 + 32 * 3 + 1 + 1;
+
+/// Shares burned past the redemption gate, paid out by `process_queued_withdrawal`.
+#[account]
+pub struct QueuedWithdrawal {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub shares: u64,
+    pub queued_at: i64,
+}
+const _QUEUED_WITHDRAWAL_SIZE: usize = 8 + 32 * 2 + 8 + 8;
+
+/// A DCA schedule: `amount` from `source` every `interval` seconds.
+#[account]
+pub struct RecurringDeposit {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub source: Pubkey,
+    pub amount: u64,
+    pub interval: i64,
+    pub next_ts: i64,
+    pub end_ts: i64,
+    pub bump: u8,
+}
+const _RECURRING_DEPOSIT_SIZE: usize = 8 + 32 * 3 + 8 * 4 + 1;
+
+/// Next nonce `owner` must sign into a `DepositIntent` for this vault.
+#[account]
+pub struct IntentNonce {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub next_nonce: u64,
+    pub bump: u8,
+}
+const _INTENT_NONCE_SIZE: usize = 8 + 32 * 2 + 8 + 1;
+
+/// The message a depositor signs off-chain for `deposit_with_intent`
+/// (borsh-encoded, 64 bytes).
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct DepositIntent {
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub min_shares: u64,
+    pub nonce: u64,
+    pub expiry: i64,
+}
+
+/// Shares `operator` may still redeem from `owner`'s share account; one per
+/// holder and vault.
+#[account]
+pub struct Operator {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub operator: Pubkey,
+    pub allowance: u64,
+    pub bump: u8,
+}
+const _OPERATOR_SIZE: usize = 8 + 32 * 3 + 8 + 1;
+
+/// One user's open deposit / redemption requests for a single epoch.
+#[account]
+pub struct EpochRequest {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub epoch: u64,
+    pub deposit_assets: u64,
+    pub redeem_shares: u64,
+}
+const _EPOCH_REQUEST_SIZE: usize = 8 + 32 * 2 + 8 * 3;
+
+/// The price a settled epoch was cleared at; claims are pro-rata against it.
+#[account]
+pub struct EpochSettlement {
+    pub vault: Pubkey,
+    pub epoch: u64,
+    pub deposit_assets: u64,
+    pub shares_minted: u64,
+    pub redeem_shares: u64,
+    pub assets_redeemed: u64,
+    pub settled_at: i64,
+}
+const _EPOCH_SETTLEMENT_SIZE: usize = 8 + 32 + 8 * 6;
+
+// ╭────────────────────────────────────────────
+// │                EVENTS                      │
+// ╰────────────────────────────────────────────
+/// `sender` signed, `owner` paid the assets, `receiver` got the shares.
+#[event]
+pub struct DepositEvent {
+    pub sender: Pubkey,
+    pub owner: Pubkey,
+    pub receiver: Pubkey,
+    pub asset_amount: u64,
+    pub shares_minted: u64,
+    pub timestamp: i64,
+}
+
+/// `sender` signed, `owner`'s shares were burned, `receiver` got the assets.
+#[event]
+pub struct WithdrawEvent {
+    pub sender: Pubkey,
+    pub owner: Pubkey,
+    pub receiver: Pubkey,
+    pub shares_burned: u64,
+    pub asset_amount: u64,
+    pub early_exit_penalty: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositRequestedEvent {
+    pub user: Pubkey,
+    pub epoch: u64,
+    pub asset_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RedeemRequestedEvent {
+    pub user: Pubkey,
+    pub epoch: u64,
+    pub shares: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EpochSettledEvent {
+    pub vault: Pubkey,
+    pub epoch: u64,
+    pub deposit_assets: u64,
+    pub shares_minted: u64,
+    pub redeem_shares: u64,
+    pub assets_redeemed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EpochClaimEvent {
+    pub user: Pubkey,
+    pub epoch: u64,
+    pub shares_minted: u64,
+    pub asset_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RecurringDepositExecutedEvent {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub keeper: Pubkey,
+    pub amount: u64,
+    pub keeper_bounty: u64,
+    pub next_ts: i64,
+}
+
+#[event]
+pub struct BasketDepositEvent {
+    pub basket: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub asset_amount: u64,
+    pub value_usd: u64,
+    pub shares_minted: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BasketWithdrawEvent {
+    pub basket: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub shares_burned: u64,
+    pub value_usd: u64,
+    pub asset_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BasketRedeemedInKindEvent {
+    pub basket: Pubkey,
+    pub user: Pubkey,
+    pub shares_burned: u64,
+    pub asset_amounts: Vec<u64>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FlashLoanEvent {
+    pub vault: Pubkey,
+    pub borrower: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+/// Also emitted with `allowance = 0` when the operator is revoked.
+#[event]
+pub struct OperatorApprovedEvent {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub operator: Pubkey,
+    pub allowance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawalQueuedEvent {
+    pub user: Pubkey,
+    pub shares: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RedemptionGateAlertEvent {
+    pub vault: Pubkey,
+    pub window_outflow: u64,
+    pub window_cap: u64,
+    pub hits: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardPoolFundedEvent {
+    pub reward_pool: Pubkey,
+    pub amount: u64,
+    pub reward_rate: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardsClaimedEvent {
+    pub user: Pubkey,
+    pub reward_mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardsSweptEvent {
+    pub reward_pool: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CompoundEvent {
+    pub vault: Pubkey,
+    pub reward_mint: Pubkey,
+    pub amount_in: u64,
+    pub proceeds: u64,
+    pub keeper_bounty: u64,
+    pub keeper: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VaultRegisteredEvent {
+    pub index: u64,
+    pub vault: Pubkey,
+    pub asset_mint: Pubkey,
+    pub vault_id: u64,
+    pub admin: Pubkey,
+}
+
+/// `reason_code` is an off-chain compliance code (e.g. sanctions list hit).
+#[event]
+pub struct HolderFrozenEvent {
+    pub vault: Pubkey,
+    pub share_account: Pubkey,
+    pub holder: Pubkey,
+    pub reason_code: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct HolderThawedEvent {
+    pub vault: Pubkey,
+    pub share_account: Pubkey,
+    pub holder: Pubkey,
+    pub reason_code: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GlobalPauseEvent {
+    pub paused: bool,
+    pub guardian: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolFeesCollectedEvent {
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub timestamp: i64,
+}
+
+// ╭────────────────────────────────────────────
+// │                ERRORS                      │
 // ╰────────────────────────────────────────────
 #[error_code]
 pub enum VaultError {
@@ -260,52 +4498,1796 @@ pub enum VaultError {
     Overflow,
     #[msg("Resulting amount is zero due to rounding.")]
     RoundingError,
+    #[msg("Vault settles in epochs; use request_deposit / request_redeem.")]
+    EpochModeActive,
+    #[msg("Vault is not in epoch mode.")]
+    EpochModeDisabled,
+    #[msg("Epoch duration must be greater than zero.")]
+    InvalidEpochDuration,
+    #[msg("Current epoch has not ended yet.")]
+    EpochNotEnded,
+    #[msg("Epoch still has unsettled requests.")]
+    PendingEpochRequests,
+    #[msg("Claim the previous epoch before requesting again.")]
+    UnclaimedEpochRequest,
+    #[msg("Nothing to claim.")]
+    NothingToClaim,
+    #[msg("Lockup period must not be negative.")]
+    InvalidLockup,
+    #[msg("Basis points must not exceed 10 000.")]
+    InvalidBps,
+    #[msg("Vault has a lockup; pass the holder's position account.")]
+    PositionRequired,
+    #[msg("Shares are still locked.")]
+    SharesLocked,
+    #[msg("Redemption gate window must be greater than zero.")]
+    InvalidGateWindow,
+    #[msg("Withdrawal exceeds the redemption gate for this window.")]
+    RedemptionGateExceeded,
+    #[msg("Token account is not owned by the expected wallet.")]
+    InvalidOwner,
+    #[msg("Reward pool / checkpoint accounts do not match.")]
+    InvalidRewardAccounts,
+    #[msg("Token account belongs to a different mint.")]
+    InvalidMint,
+    #[msg("Nothing to compound.")]
+    NothingToCompound,
+    #[msg("Swap returned less than the requested minimum.")]
+    SlippageExceeded,
+    #[msg("Swap program does not match the vault configuration.")]
+    InvalidSwapProgram,
+    #[msg("Asset mint is not on the protocol allow-list.")]
+    AssetMintNotAllowed,
+    #[msg("Protocol allow-list is full.")]
+    AllowListFull,
+    #[msg("All vaults are paused by the protocol guardian.")]
+    ProtocolPaused,
+    #[msg("Share token name, symbol or URI is too long.")]
+    InvalidMetadata,
+    #[msg("Signer is not the vault's transfer-hook authority.")]
+    InvalidHookAuthority,
+    #[msg("Redemption exceeds the operator's share allowance.")]
+    AllowanceExceeded,
+    #[msg("Vault asset is not native SOL.")]
+    NotNativeVault,
+    #[msg("Pass the Asset-A token account, or the SOL accounts on a native vault.")]
+    AssetAccountRequired,
+    #[msg("Flash loans are paused for this vault.")]
+    FlashLoansPaused,
+    #[msg("A flash loan is already open.")]
+    FlashLoanActive,
+    #[msg("Flash loan is not repaid later in this transaction.")]
+    FlashLoanNotRepaid,
+    #[msg("No flash loan to repay.")]
+    NoFlashLoan,
+    #[msg("Deposit intent signature or accounts are invalid.")]
+    InvalidIntent,
+    #[msg("Deposit intent has expired.")]
+    IntentExpired,
+    #[msg("Deposit intent nonce was already used.")]
+    IntentNonceUsed,
+    #[msg("Pass one group of recipient accounts per amount.")]
+    BatchLengthMismatch,
+    #[msg("Recurring deposit accounts or schedule are invalid.")]
+    InvalidRecurringDeposit,
+    #[msg("Recurring deposit is not due yet or has ended.")]
+    RecurringDepositNotDue,
+    #[msg("Price history interval and TWAP window must be positive.")]
+    InvalidPriceInterval,
+    #[msg("Price history does not cover the requested TWAP window.")]
+    InsufficientPriceHistory,
+    #[msg("Oracle account or price is invalid.")]
+    InvalidOracle,
+    #[msg("Pass the vault's oracle account.")]
+    OracleRequired,
+    #[msg("Oracle price is older than the vault allows.")]
+    OracleStale,
+    #[msg("Oracle confidence interval is too wide.")]
+    OracleConfidenceTooWide,
+    #[msg("Deposit would exceed the vault's USD cap.")]
+    DepositCapExceeded,
+    #[msg("Account is not a constituent of this basket.")]
+    InvalidBasketAsset,
+    #[msg("Basket already holds the maximum number of constituents.")]
+    BasketFull,
+    #[msg("Constituents can only change while the basket has no shares.")]
+    BasketNotEmpty,
+    #[msg("Basket target weights must be positive and add up to 100 %.")]
+    InvalidBasketWeights,
+    #[msg("Basket holds too little of the requested constituent.")]
+    InsufficientBasketLiquidity,
+    #[msg("Reward pools need soulbound shares or the share transfer hook.")]
+    RewardsNeedTransferHook,
+    #[msg("Vault already runs the maximum number of reward pools.")]
+    TooManyRewardPools,
+    #[msg("Lockups need soulbound shares or the share transfer hook.")]
+    LockupNeedsTransferHook,
+    #[msg("The constituent would leave its target weight band.")]
+    BasketWeightExceeded,
+    #[msg("Signer is not the share owner's approved operator.")]
+    NotOperator,
+    #[msg("The vault publishes a share price feed; pass it.")]
+    SharePriceFeedRequired,
+    #[msg("Feed staleness must be at least one slot.")]
+    InvalidFeedStaleness,
+    #[msg("Division by zero.")]
+    DivisionByZero,
+    #[msg("The vault keeps a price history; pass it.")]
+    PriceHistoryRequired,
+    #[msg("The transfer-hook program is not share-hook.")]
+    InvalidTransferHookProgram,
+    #[msg("A batch recipient's position account is not its PDA.")]
+    InvalidBatchAccounts,
+}
+
+// ╭────────────────────────────────────────────
+// │              CONTEXTS                      │
+// ╰────────────────────────────────────────────
+#[derive(Accounts)]
+#[instruction(vault_bump: u8, auth_bump: u8, vault_id: u64)]
+pub struct InitializeVault<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"vault_state", asset_mint.key().as_ref(), &vault_id.to_le_bytes()],
+        bump,
+        space = _VAULT_STATE_SIZE
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// CHECK: PDA, only used as signer
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump = auth_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+     init,
+     payer = admin,                      // <── the fee payer
+     seeds = [
+         b"vault_asset",
+         asset_mint.key().as_ref(),
+         vault_state.key().as_ref(),
+         &vault_id.to_le_bytes()
+     ],
+     bump,
+    token::mint = asset_mint,
+     token::authority = vault_authority
+    )]
+    pub vault_asset_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub asset_mint: Box<Account<'info, Mint>>,
+    /// CHECK: fresh keypair, created in the handler as a Token-2022 mint with
+    /// decimals = asset_mint.decimals and vault_authority as mint/freeze authority
+    #[account(mut)]
+    pub share_mint: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"vault_registry".as_ref(), &protocol_config.vault_count.to_le_bytes()],
+        bump,
+        space = _VAULT_REGISTRY_ENTRY_SIZE
+    )]
+    pub vault_registry: Box<Account<'info, VaultRegistryEntry>>,
+
+    /// CHECK: optional transfer-hook program written into the share mint
+    #[account(
+        executable,
+        address = SHARE_HOOK_PROGRAM_ID @ VaultError::InvalidTransferHookProgram
+    )]
+    pub transfer_hook_program: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account,
+        has_one = share_mint
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mut)]
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    /// Left out by `deposit_sol`, which pays in lamports.
+    #[account(mut)]
+    pub user_asset_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// CHECK: any wallet; it receives the shares (and the rent of a
+    /// finished recurring schedule)
+    #[account(mut)]
+    pub receiver: UncheckedAccount<'info>,
+
+    /// The receiver's share ATA, created on their first deposit.
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = share_mint,
+        associated_token::authority = receiver,
+        associated_token::token_program = share_token_program
+    )]
+    pub receiver_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// CHECK: signer PDA for CPIs
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [
+            b"position",
+            vault_state.key().as_ref(),
+            receiver.key().as_ref()
+        ],
+        bump,
+        space = _POSITION_SIZE
+    )]
+    pub position: Option<Account<'info, Position>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"user_position", vault_state.key().as_ref(), receiver.key().as_ref()],
+        bump,
+        space = _USER_POSITION_SIZE
+    )]
+    pub user_position: Option<Box<Account<'info, UserPosition>>>,
+
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Option<Box<Account<'info, PriceHistory>>>,
+
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Option<Box<Account<'info, SharePriceFeed>>>,
+
+    /// CHECK: Asset A price account, parsed by `OraclePrice::load`; needed
+    /// while a USD deposit cap is set
+    #[account(address = vault_state.oracle @ VaultError::InvalidOracle)]
+    pub asset_oracle: Option<UncheckedAccount<'info>>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Replay protection for `deposit_with_intent`.
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"intent_nonce", vault_state.key().as_ref(), receiver.key().as_ref()],
+        bump,
+        space = _INTENT_NONCE_SIZE
+    )]
+    pub intent_nonce: Option<Box<Account<'info, IntentNonce>>>,
+
+    /// CHECK: instructions sysvar, read by `deposit_with_intent`
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    /// Schedule run by `execute_recurring_deposit`.
+    #[account(
+        mut,
+        seeds = [b"recurring", vault_state.key().as_ref(), receiver.key().as_ref()],
+        bump = recurring_deposit.bump
+    )]
+    pub recurring_deposit: Option<Box<Account<'info, RecurringDeposit>>>,
+
+    /// Receives the keeper bounty of `execute_recurring_deposit`.
+    #[account(
+        mut,
+        constraint = keeper_asset_account.mint == vault_state.asset_mint @ VaultError::InvalidMint
+    )]
+    pub keeper_asset_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    #[account(mut, signer)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRecurringDeposit<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        token::mint = vault_state.asset_mint,
+        token::authority = owner
+    )]
+    pub user_asset_account: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"recurring", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump,
+        space = _RECURRING_DEPOSIT_SIZE
+    )]
+    pub recurring_deposit: Account<'info, RecurringDeposit>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRecurringDeposit<'info> {
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        seeds = [b"recurring", recurring_deposit.vault.as_ref(), owner.key().as_ref()],
+        bump = recurring_deposit.bump
+    )]
+    pub recurring_deposit: Account<'info, RecurringDeposit>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BatchDeposit<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account,
+        has_one = share_mint
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Option<Box<Account<'info, SharePriceFeed>>>,
+
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Option<Box<Account<'info, PriceHistory>>>,
+
+    /// CHECK: Asset A price account, parsed by `OraclePrice::load`; needed
+    /// while a USD deposit cap is set
+    #[account(address = vault_state.oracle @ VaultError::InvalidOracle)]
+    pub asset_oracle: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    /// Funding source for the whole batch.
+    #[account(mut)]
+    pub user_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// CHECK: signer PDA for CPIs
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Pays the rent of recipient PDAs created by the batch.
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account,
+        has_one = share_mint
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mut)]
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    /// Any Asset-A account, e.g. the owner's cold wallet; left out by
+    /// `withdraw_sol`, which pays `sol_receiver` in lamports.
+    #[account(mut)]
+    pub receiver_asset_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = share_mint)]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// CHECK: signer PDA for CPIs
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [
+            b"position",
+            vault_state.key().as_ref(),
+            user_share_account.owner.as_ref()
+        ],
+        bump,
+        space = _POSITION_SIZE
+    )]
+    pub position: Option<Account<'info, Position>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [
+            b"queued_withdrawal",
+            vault_state.key().as_ref(),
+            user_share_account.owner.as_ref()
+        ],
+        bump,
+        space = _QUEUED_WITHDRAWAL_SIZE
+    )]
+    pub queued_withdrawal: Option<Account<'info, QueuedWithdrawal>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"user_position",
+            vault_state.key().as_ref(),
+            user_share_account.owner.as_ref()
+        ],
+        bump = user_position.bump
+    )]
+    pub user_position: Option<Box<Account<'info, UserPosition>>>,
+
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Option<Box<Account<'info, PriceHistory>>>,
+
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Option<Box<Account<'info, SharePriceFeed>>>,
+
+    /// Set when `user` redeems as the operator approved by the share owner.
+    #[account(
+        mut,
+        seeds = [
+            b"operator",
+            vault_state.key().as_ref(),
+            user_share_account.owner.as_ref()
+        ],
+        bump = operator.bump,
+        constraint = operator.operator == user.key() @ VaultError::NotOperator
+    )]
+    pub operator: Option<Account<'info, Operator>>,
+
+    #[account(address = token::spl_token::native_mint::ID)]
+    pub native_mint: Option<Box<Account<'info, Mint>>>,
+
+    /// wSOL scratch account for `withdraw_sol`, closed in the same instruction.
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"sol_unwrap", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = vault_authority,
+        token::token_program = token_program
+    )]
+    pub sol_unwrap_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    #[account(mut)]
+    pub sol_receiver: Option<SystemAccount<'info>>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(mut, signer)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveOperator<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        token::mint = vault_state.share_mint,
+        token::authority = owner
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// CHECK: wallet or program PDA allowed to redeem for `owner`
+    pub operator: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [b"operator", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump,
+        space = _OPERATOR_SIZE
+    )]
+    pub operator_allowance: Account<'info, Operator>,
+
+    /// CHECK: becomes the SPL delegate of `user_share_account`
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeOperator<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        token::mint = vault_state.share_mint,
+        token::authority = owner
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        seeds = [b"operator", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump = operator_allowance.bump
+    )]
+    pub operator_allowance: Account<'info, Operator>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub share_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct FlashBorrow<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mut)]
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub borrower_asset_account: Account<'info, TokenAccount>,
+
+    /// CHECK: signer PDA for CPIs
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub borrower: Signer<'info>,
+
+    /// CHECK: instructions sysvar, searched for the matching `flash_repay`
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// `vault_state` must stay the first account, `flash_borrow` matches on it.
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Option<Box<Account<'info, SharePriceFeed>>>,
+
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Option<Box<Account<'info, PriceHistory>>>,
+
+    #[account(mut)]
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub borrower_asset_account: Account<'info, TokenAccount>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub borrower: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ViewUserPosition<'info> {
+    pub user_position: Account<'info, UserPosition>,
+}
+
+#[derive(Accounts)]
+pub struct CreatePriceHistory<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump,
+        space = _PRICE_HISTORY_SIZE
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PriceHistoryAdmin<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SyncPriceHistory<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+}
+
+#[derive(Accounts)]
+pub struct ViewPriceHistory<'info> {
+    pub price_history: Box<Account<'info, PriceHistory>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateSharePriceFeed<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump,
+        space = _SHARE_PRICE_FEED_SIZE
+    )]
+    pub share_price_feed: Account<'info, SharePriceFeed>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SharePriceFeedAdmin<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Account<'info, SharePriceFeed>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshSharePriceFeed<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Account<'info, SharePriceFeed>,
+}
+
+#[derive(Accounts)]
+pub struct ViewNavUsd<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// CHECK: parsed and validated by `OraclePrice::load`
+    #[account(address = vault_state.oracle @ VaultError::InvalidOracle)]
+    pub oracle: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(basket_id: u64)]
+pub struct InitializeBasket<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"basket", admin.key().as_ref(), &basket_id.to_le_bytes()],
+        bump,
+        space = _BASKET_STATE_SIZE
+    )]
+    pub basket: Box<Account<'info, BasketState>>,
+
+    /// CHECK: PDA that owns the constituent accounts and mints the shares
+    #[account(seeds = [b"basket_authority", basket.key().as_ref()], bump)]
+    pub basket_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"basket_shares", basket.key().as_ref()],
+        bump,
+        mint::decimals = USD_DECIMALS,
+        mint::authority = basket_authority,
+        mint::token_program = share_token_program
+    )]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub share_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct BasketAdmin<'info> {
+    #[account(mut, has_one = admin)]
+    pub basket: Box<Account<'info, BasketState>>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddBasketAsset<'info> {
+    #[account(mut, has_one = admin)]
+    pub basket: Box<Account<'info, BasketState>>,
+
+    /// CHECK: PDA that owns the constituent accounts
+    #[account(
+        seeds = [b"basket_authority", basket.key().as_ref()],
+        bump = basket.authority_bump
+    )]
+    pub basket_authority: UncheckedAccount<'info>,
+
+    pub asset_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"basket_asset", basket.key().as_ref(), asset_mint.key().as_ref()],
+        bump,
+        token::mint = asset_mint,
+        token::authority = basket_authority
+    )]
+    pub basket_asset_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: USD price account (`PriceUpdateV2` layout), parsed on use
+    pub oracle: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct BasketDeposit<'info> {
+    #[account(mut, has_one = share_mint)]
+    pub basket: Box<Account<'info, BasketState>>,
+
+    /// CHECK: PDA that mints the shares
+    #[account(
+        seeds = [b"basket_authority", basket.key().as_ref()],
+        bump = basket.authority_bump
+    )]
+    pub basket_authority: UncheckedAccount<'info>,
+
+    /// Checked against the basket's constituents.
+    #[account(mut)]
+    pub basket_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        token::mint = share_mint,
+        token::token_program = share_token_program
+    )]
+    pub receiver_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct BasketWithdraw<'info> {
+    #[account(mut, has_one = share_mint)]
+    pub basket: Box<Account<'info, BasketState>>,
+
+    /// CHECK: PDA that owns the constituent accounts
+    #[account(
+        seeds = [b"basket_authority", basket.key().as_ref()],
+        bump = basket.authority_bump
+    )]
+    pub basket_authority: UncheckedAccount<'info>,
+
+    /// Checked against the basket's constituents.
+    #[account(mut)]
+    pub basket_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub receiver_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        token::mint = share_mint,
+        token::authority = user,
+        token::token_program = share_token_program
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct BasketRedeemInKind<'info> {
+    #[account(mut, has_one = share_mint)]
+    pub basket: Box<Account<'info, BasketState>>,
+
+    /// CHECK: PDA that owns the constituent accounts
+    #[account(
+        seeds = [b"basket_authority", basket.key().as_ref()],
+        bump = basket.authority_bump
+    )]
+    pub basket_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        token::mint = share_mint,
+        token::authority = user,
+        token::token_program = share_token_program
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ViewBasket<'info> {
+    pub basket: Box<Account<'info, BasketState>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminAction<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account()]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct HolderFreeze<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin,
+        has_one = share_mint
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(mut, token::mint = share_mint)]
+    pub holder_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// CHECK: freeze authority of the share mint
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub admin: Signer<'info>,
+
+    pub share_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateShareMetadata<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin,
+        has_one = share_mint
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// CHECK: metadata update authority
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RequestDeposit<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mut)]
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_asset_account: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"epoch_request", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = _EPOCH_REQUEST_SIZE
+    )]
+    pub epoch_request: Account<'info, EpochRequest>,
+
+    /// CHECK: Asset A price account, parsed by `OraclePrice::load`; needed
+    /// while a USD deposit cap is set
+    #[account(address = vault_state.oracle @ VaultError::InvalidOracle)]
+    pub asset_oracle: Option<UncheckedAccount<'info>>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RequestRedeem<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = share_mint
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mut, token::mint = share_mint)]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"epoch_request", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = _EPOCH_REQUEST_SIZE
+    )]
+    pub epoch_request: Account<'info, EpochRequest>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"position", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = _POSITION_SIZE
+    )]
+    pub position: Option<Account<'info, Position>>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleEpoch<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Option<Box<Account<'info, SharePriceFeed>>>,
+
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Option<Box<Account<'info, PriceHistory>>>,
+
+    #[account(
+        init,
+        payer = cranker,
+        seeds = [
+            b"epoch",
+            vault_state.key().as_ref(),
+            &vault_state.current_epoch.to_le_bytes()
+        ],
+        bump,
+        space = _EPOCH_SETTLEMENT_SIZE
+    )]
+    pub epoch_settlement: Account<'info, EpochSettlement>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimEpoch<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account,
+        has_one = share_mint
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mut)]
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_asset_account: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = share_mint, token::authority = user)]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"epoch_request", vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub epoch_request: Account<'info, EpochRequest>,
+
+    #[account(
+        seeds = [
+            b"epoch",
+            vault_state.key().as_ref(),
+            &epoch_request.epoch.to_le_bytes()
+        ],
+        bump
+    )]
+    pub epoch_settlement: Account<'info, EpochSettlement>,
+
+    /// CHECK: signer PDA for CPIs
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"position", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = _POSITION_SIZE
+    )]
+    pub position: Option<Account<'info, Position>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"user_position", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = _USER_POSITION_SIZE
+    )]
+    pub user_position: Option<Box<Account<'info, UserPosition>>>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessQueuedWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Option<Box<Account<'info, SharePriceFeed>>>,
+
+    #[account(mut)]
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = owner_asset_account.owner == owner.key() @ VaultError::InvalidOwner
+    )]
+    pub owner_asset_account: Account<'info, TokenAccount>,
+
+    /// Closed once the last queued share is paid.
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [b"queued_withdrawal", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub queued_withdrawal: Account<'info, QueuedWithdrawal>,
+
+    #[account(
+        mut,
+        seeds = [b"user_position", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump = user_position.bump
+    )]
+    pub user_position: Option<Box<Account<'info, UserPosition>>>,
+
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Option<Box<Account<'info, PriceHistory>>>,
+
+    /// CHECK: receives the payout and the queue account's rent
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// CHECK: signer PDA for CPIs
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRewardPool<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"reward_pool", vault_state.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+        space = _REWARD_POOL_SIZE
+    )]
+    pub reward_pool: Account<'info, RewardPool>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"reward_vault", reward_pool.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = vault_authority
+    )]
+    pub reward_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: PDA, only used as token authority
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub reward_mint: Box<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewardPool<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        has_one = reward_vault,
+        constraint = reward_pool.vault == vault_state.key() @ VaultError::InvalidRewardAccounts
+    )]
+    pub reward_pool: Account<'info, RewardPool>,
+
+    #[account(mut)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub funder_token_account: Account<'info, TokenAccount>,
+
+    pub admin: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
 }
 
-// ╭────────────────────────────────────────────
-// │              CONTEXTS                      │
-// ╰────────────────────────────────────────────
 #[derive(Accounts)]
-#[instruction(vault_bump: u8, auth_bump: u8)]
-pub struct InitializeVault<'info> {
+pub struct SetRewardRate<'info> {
     #[account(
-        init,
-        payer = admin,
-        seeds = [b"vault_state", asset_mint.key().as_ref()],
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        constraint = reward_pool.vault == vault_state.key() @ VaultError::InvalidRewardAccounts
+    )]
+    pub reward_pool: Account<'info, RewardPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRewardCheckpoint<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        constraint = reward_pool.vault == vault_state.key() @ VaultError::InvalidRewardAccounts
+    )]
+    pub reward_pool: Account<'info, RewardPool>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"reward_checkpoint", reward_pool.key().as_ref(), user.key().as_ref()],
         bump,
-        space = _VAULT_STATE_SIZE
+        space = _REWARD_CHECKPOINT_SIZE
+    )]
+    pub reward_checkpoint: Account<'info, RewardCheckpoint>,
+
+    #[account(
+        constraint = user_share_account.owner == user.key() @ VaultError::InvalidOwner,
+        constraint = user_share_account.mint == vault_state.share_mint @ VaultError::InvalidMint
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SyncTransferCheckpoints<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
     )]
     pub vault_state: Account<'info, VaultState>,
 
-    /// CHECK: PDA, only used as signer
+    pub hook_authority: Signer<'info>,
+
+    #[account(
+        constraint = source_share_account.mint == vault_state.share_mint @ VaultError::InvalidMint
+    )]
+    pub source_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        constraint = destination_share_account.mint == vault_state.share_mint @ VaultError::InvalidMint
+    )]
+    pub destination_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// CHECK: the sender's `UserPosition`, may not exist
+    #[account(
+        mut,
+        seeds = [
+            b"user_position",
+            vault_state.key().as_ref(),
+            source_share_account.owner.as_ref()
+        ],
+        bump
+    )]
+    pub source_user_position: UncheckedAccount<'info>,
+
+    /// CHECK: the receiver's `UserPosition`, may not exist
+    #[account(
+        mut,
+        seeds = [
+            b"user_position",
+            vault_state.key().as_ref(),
+            destination_share_account.owner.as_ref()
+        ],
+        bump
+    )]
+    pub destination_user_position: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        has_one = reward_vault,
+        constraint = reward_pool.vault == vault_state.key() @ VaultError::InvalidRewardAccounts
+    )]
+    pub reward_pool: Account<'info, RewardPool>,
+
+    #[account(mut)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_checkpoint", reward_pool.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub reward_checkpoint: Account<'info, RewardCheckpoint>,
+
+    #[account(
+        constraint = user_share_account.owner == user.key() @ VaultError::InvalidOwner,
+        constraint = user_share_account.mint == vault_state.share_mint @ VaultError::InvalidMint
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut)]
+    pub user_reward_account: Account<'info, TokenAccount>,
+
+    /// CHECK: signer PDA for CPIs
     #[account(
         seeds = [b"vault_authority", vault_state.key().as_ref()],
-        bump = auth_bump
+        bump
     )]
     pub vault_authority: UncheckedAccount<'info>,
 
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SweepUnclaimableRewards<'info> {
     #[account(
-     init,
-     payer = admin,                      // <── the fee payer
-     seeds = [b"vault_asset", asset_mint.key().as_ref(), vault_state.key().as_ref()],
-     bump,
-    token::mint = asset_mint,
-     token::authority = vault_authority
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
     )]
-    pub vault_asset_account: Box<Account<'info, TokenAccount>>,
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        has_one = reward_vault,
+        constraint = reward_pool.vault == vault_state.key() @ VaultError::InvalidRewardAccounts
+    )]
+    pub reward_pool: Account<'info, RewardPool>,
+
+    #[account(mut)]
+    pub reward_vault: Account<'info, TokenAccount>,
 
     #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    /// CHECK: signer PDA for CPIs
+    #[account(
+        seeds = [b"vault_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
     pub admin: Signer<'info>,
 
-    pub asset_mint: Box<Account<'info, Mint>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CreateHarvestAccount<'info> {
+    #[account(
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = admin
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
     #[account(
         init,
         payer = admin,
-        mint::decimals = asset_mint.decimals,
-        mint::authority = vault_authority,
-        mint::freeze_authority = vault_authority
+        seeds = [b"harvest", vault_state.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = swap_authority
+    )]
+    pub harvest_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"harvest_config", vault_state.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+        space = _HARVEST_CONFIG_SIZE
+    )]
+    pub harvest_config: Box<Account<'info, HarvestConfig>>,
+
+    /// CHECK: USD price account of the reward mint, parsed on use
+    pub reward_oracle: UncheckedAccount<'info>,
+
+    /// CHECK: PDA, signs for harvest accounts only
+    #[account(
+        seeds = [b"swap_authority", vault_state.key().as_ref()],
+        bump
     )]
-    pub share_mint: Box<Account<'info, Mint>>,
+    pub swap_authority: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = reward_mint.key() != vault_state.asset_mint @ VaultError::InvalidMint
+    )]
+    pub reward_mint: Box<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
 
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
@@ -313,21 +6295,62 @@ pub struct InitializeVault<'info> {
 }
 
 #[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(mut)]
-    pub vault_state: Account<'info, VaultState>,
+pub struct Compound<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account
+    )]
+    pub vault_state: Box<Account<'info, VaultState>>,
 
-    #[account(mut)]
-    pub vault_asset_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"share_price_feed", vault_state.key().as_ref()],
+        bump = share_price_feed.bump
+    )]
+    pub share_price_feed: Option<Box<Account<'info, SharePriceFeed>>>,
 
-    #[account(mut)]
-    pub user_asset_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"price_history", vault_state.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Option<Box<Account<'info, PriceHistory>>>,
 
     #[account(mut)]
-    pub user_share_account: Account<'info, TokenAccount>,
+    pub vault_asset_account: Box<Account<'info, TokenAccount>>,
 
-    #[account(mut)]
-    pub share_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"harvest", vault_state.key().as_ref(), harvest_account.mint.as_ref()],
+        bump
+    )]
+    pub harvest_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [b"harvest_config", vault_state.key().as_ref(), harvest_account.mint.as_ref()],
+        bump = harvest_config.bump
+    )]
+    pub harvest_config: Box<Account<'info, HarvestConfig>>,
+
+    /// CHECK: Asset A price account, parsed by `OraclePrice::load`
+    pub asset_oracle: UncheckedAccount<'info>,
+
+    /// CHECK: reward price account, parsed by `OraclePrice::parse`
+    #[account(address = harvest_config.reward_oracle @ VaultError::InvalidOracle)]
+    pub reward_oracle: UncheckedAccount<'info>,
+
+    /// CHECK: PDA, signs the swap CPI
+    #[account(
+        seeds = [b"swap_authority", vault_state.key().as_ref()],
+        bump
+    )]
+    pub swap_authority: UncheckedAccount<'info>,
 
     /// CHECK: signer PDA for CPIs
     #[account(
@@ -336,28 +6359,99 @@ pub struct Deposit<'info> {
     )]
     pub vault_authority: UncheckedAccount<'info>,
 
-    #[account(mut, signer)]
-    pub user: Signer<'info>,
+    #[account(
+        mut,
+        constraint = keeper_asset_account.mint == vault_state.asset_mint @ VaultError::InvalidMint
+    )]
+    pub keeper_asset_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(seeds = [b"protocol_config"], bump = protocol_config.bump)]
+    pub protocol_config: Box<Account<'info, ProtocolConfig>>,
+
+    pub keeper: Signer<'info>,
+
+    /// CHECK: checked against the vault's configured swap program
+    #[account(
+        executable,
+        address = vault_state.swap_program @ VaultError::InvalidSwapProgram
+    )]
+    pub swap_program: UncheckedAccount<'info>,
 
     pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(mut)]
-    pub vault_state: Account<'info, VaultState>,
+pub struct InitializeProtocol<'info> {
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"protocol_config"],
+        bump,
+        space = _PROTOCOL_CONFIG_SIZE
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
 
     #[account(mut)]
-    pub vault_asset_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
 
-    #[account(mut)]
-    pub user_asset_account: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
 
-    #[account(mut)]
-    pub user_share_account: Account<'info, TokenAccount>,
+#[derive(Accounts)]
+pub struct ProtocolAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = owner
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GuardianAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = guardian
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = owner
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vault_state",
+            vault_state.asset_mint.as_ref(),
+            &vault_state.vault_id.to_le_bytes()
+        ],
+        bump = vault_state.bump,
+        has_one = vault_asset_account
+    )]
+    pub vault_state: Account<'info, VaultState>,
 
     #[account(mut)]
-    pub share_mint: Account<'info, Mint>,
+    pub vault_asset_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == vault_state.asset_mint @ VaultError::InvalidMint
+    )]
+    pub destination: Account<'info, TokenAccount>,
 
     /// CHECK: signer PDA for CPIs
     #[account(
@@ -366,17 +6460,280 @@ pub struct Withdraw<'info> {
     )]
     pub vault_authority: UncheckedAccount<'info>,
 
-    #[account(mut, signer)]
-    pub user: Signer<'info>,
+    pub owner: Signer<'info>,
 
     pub token_program: Program<'info, Token>,
 }
 
-#[derive(Accounts)]
-pub struct AdminAction<'info> {
-    #[account(mut, has_one = admin)]
-    pub vault_state: Account<'info, VaultState>,
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    #[account()]
-    pub admin: Signer<'info>,
-}
\ No newline at end of file
+    const P: u64 = PRICE_PRECISION;
+
+    fn history(interval: i64) -> PriceHistory {
+        PriceHistory {
+            vault: Pubkey::new_unique(),
+            interval,
+            head: 0,
+            len: 0,
+            bump: 255,
+            snapshots: [PriceSnapshot::default(); PRICE_HISTORY_LEN],
+        }
+    }
+
+    #[test]
+    fn twap_weights_each_price_by_how_long_it_held() {
+        let mut history = history(10);
+        assert!(history.record(100, 1_000, 1_000));
+        assert!(!history.record(105, 9_000, 1_000));
+        assert!(history.record(110, 2_000, 1_000));
+
+        // 1.0 for 10s, then 2.0 for 30s.
+        assert_eq!(history.twap(140, 40).unwrap(), P * 7 / 4);
+        assert_eq!(history.twap(140, 20).unwrap(), 2 * P);
+    }
+
+    #[test]
+    fn twap_after_the_ring_wraps_uses_the_latest_snapshots() {
+        let mut history = history(1);
+        let total = PRICE_HISTORY_LEN as i64 + 5;
+        for t in 0..total {
+            // price t + 1 recorded at t
+            assert!(history.record(t, (t as u64 + 1) * 1_000, 1_000));
+        }
+        assert_eq!(history.len as usize, PRICE_HISTORY_LEN);
+        assert_eq!(history.head, 5);
+        assert_eq!(history.at(0).timestamp, 5);
+        assert_eq!(history.at(PRICE_HISTORY_LEN - 1).timestamp, total - 1);
+
+        // the last two snapshots, each held for one second
+        assert_eq!(
+            history.twap(total, 2).unwrap(),
+            (total as u64 - 1 + total as u64) * P / 2
+        );
+        // the whole ring: prices 6..=total
+        let window = PRICE_HISTORY_LEN as i64;
+        let sum: u64 = (6..=total as u64).sum();
+        assert_eq!(
+            history.twap(total, window).unwrap(),
+            sum * P / window as u64
+        );
+    }
+
+    #[test]
+    fn twap_rejects_windows_older_than_the_oldest_snapshot() {
+        let mut history = history(1);
+        assert_eq!(
+            history.twap(100, 10).unwrap_err(),
+            VaultError::InsufficientPriceHistory.into()
+        );
+
+        for t in 0..PRICE_HISTORY_LEN as i64 + 3 {
+            history.record(t, 1_000, 1_000);
+        }
+        // snapshot 0..=2 were overwritten
+        let now = PRICE_HISTORY_LEN as i64 + 3;
+        assert_eq!(history.twap(now, now - 3).unwrap(), P);
+        assert_eq!(
+            history.twap(now, now - 2).unwrap_err(),
+            VaultError::InsufficientPriceHistory.into()
+        );
+        assert_eq!(
+            history.twap(now, 0).unwrap_err(),
+            VaultError::InvalidPriceInterval.into()
+        );
+    }
+
+    #[test]
+    fn mul_div_rounds_down_through_u128() {
+        assert_eq!(mul_div(10, 1, 3).unwrap(), 3);
+        assert_eq!(mul_div(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(
+            mul_div(u64::MAX, 2, 1).unwrap_err(),
+            VaultError::Overflow.into()
+        );
+        assert_eq!(
+            mul_div(1, 1, 0).unwrap_err(),
+            VaultError::DivisionByZero.into()
+        );
+    }
+
+    fn reward_pool(acc_reward_per_share: u128, staked_shares: u64) -> RewardPool {
+        RewardPool {
+            vault: Pubkey::new_unique(),
+            reward_mint: Pubkey::new_unique(),
+            reward_vault: Pubkey::new_unique(),
+            reward_rate: 0,
+            acc_reward_per_share,
+            last_update_ts: 0,
+            total_funded: 0,
+            total_accrued: 0,
+            total_claimed: 0,
+            staked_shares,
+            unclaimable: 0,
+        }
+    }
+
+    fn checkpoint(shares: u64) -> RewardCheckpoint {
+        RewardCheckpoint {
+            pool: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            shares,
+            acc_snapshot: 0,
+            pending: 0,
+        }
+    }
+
+    #[test]
+    fn checkpoint_sync_books_rewards_and_moves_staked_shares() {
+        // two reward tokens per share since the last sync
+        let mut pool = reward_pool(2 * REWARD_PRECISION, 300);
+        let mut checkpoint = checkpoint(100);
+
+        checkpoint.sync(&mut pool, 100, 150).unwrap();
+        assert_eq!(checkpoint.pending, 200);
+        assert_eq!(checkpoint.shares, 150);
+        assert_eq!(checkpoint.acc_snapshot, 2 * REWARD_PRECISION);
+        assert_eq!(pool.staked_shares, 350);
+        assert_eq!(pool.unclaimable, 0);
+
+        // nothing accrued since: a second sync books nothing
+        checkpoint.sync(&mut pool, 150, 150).unwrap();
+        assert_eq!(checkpoint.pending, 200);
+        assert_eq!(pool.staked_shares, 350);
+    }
+
+    #[test]
+    fn checkpoint_sync_leaves_rewards_of_vanished_shares_unclaimable() {
+        let mut pool = reward_pool(2 * REWARD_PRECISION, 100);
+        let mut checkpoint = checkpoint(100);
+
+        // 60 shares left without a sync: only the 40 still held earn
+        checkpoint.sync(&mut pool, 40, 40).unwrap();
+        assert_eq!(checkpoint.pending, 80);
+        assert_eq!(pool.unclaimable, 120);
+        assert_eq!(pool.staked_shares, 40);
+    }
+
+    fn position(locked_shares: u64, unlock_ts: i64) -> Position {
+        Position {
+            vault: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            locked_shares,
+            unlock_ts,
+        }
+    }
+
+    #[test]
+    fn release_locked_shares_spends_free_shares_first() {
+        let mut position = position(60, 1_000);
+        assert_eq!(
+            release_locked_shares(&mut position, 100, 30, 500).unwrap(),
+            0
+        );
+        assert_eq!(position.locked_shares, 60);
+
+        // 40 free shares, then 30 locked ones
+        assert_eq!(
+            release_locked_shares(&mut position, 100, 70, 500).unwrap(),
+            30
+        );
+        assert_eq!(position.locked_shares, 30);
+    }
+
+    #[test]
+    fn release_locked_shares_caps_the_lock_at_the_balance() {
+        let mut position = position(60, 1_000);
+        assert_eq!(
+            release_locked_shares(&mut position, 50, 20, 500).unwrap(),
+            20
+        );
+        assert_eq!(position.locked_shares, 30);
+    }
+
+    #[test]
+    fn release_locked_shares_clears_an_expired_lock() {
+        let mut position = position(60, 1_000);
+        assert_eq!(
+            release_locked_shares(&mut position, 100, 100, 1_000).unwrap(),
+            0
+        );
+        assert_eq!(position.locked_shares, 0);
+    }
+
+    /// `PriceUpdateV2` data with a `Full` (1) or `Partial` (0) level.
+    fn price_update(level: u8, price: i64, conf: u64, expo: i32, publish_time: i64) -> Vec<u8> {
+        let mut data = PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[0; 32]); // write authority
+        data.push(level);
+        data.extend_from_slice(&[0; 32]); // feed id
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&conf.to_le_bytes());
+        data.extend_from_slice(&expo.to_le_bytes());
+        data.extend_from_slice(&publish_time.to_le_bytes());
+        data.extend_from_slice(&[0; 16]); // prev publish time, ema price
+        data
+    }
+
+    fn parse(mut data: Vec<u8>, now: i64) -> Result<OraclePrice> {
+        let (key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut lamports = 0;
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        OraclePrice::parse(&info, 60, 100, now) // 60 s, 1 %
+    }
+
+    #[test]
+    fn oracle_parse_reads_a_verified_update() {
+        // $1.00 ± $0.005
+        let price = parse(price_update(1, 100_000_000, 500_000, -8, 1_000), 1_060).unwrap();
+        assert_eq!(price.price, 100_000_000);
+        assert_eq!(price.conf, 500_000);
+        assert_eq!(price.expo, -8);
+        assert_eq!(price.publish_time, 1_000);
+        assert_eq!(price.usd_value(2_000_000, 6).unwrap(), 2_000_000);
+    }
+
+    #[test]
+    fn oracle_parse_enforces_the_limits() {
+        assert_eq!(
+            parse(price_update(1, 100_000_000, 0, -8, 1_000), 1_061).err(),
+            Some(VaultError::OracleStale.into())
+        );
+        assert_eq!(
+            parse(price_update(1, 100_000_000, 1_000_001, -8, 1_000), 1_000).err(),
+            Some(VaultError::OracleConfidenceTooWide.into())
+        );
+        assert_eq!(
+            parse(price_update(1, 0, 0, -8, 1_000), 1_000).err(),
+            Some(VaultError::InvalidOracle.into())
+        );
+    }
+
+    #[test]
+    fn oracle_parse_rejects_unverified_or_foreign_accounts() {
+        assert_eq!(
+            parse(price_update(0, 100_000_000, 0, -8, 1_000), 1_000).err(),
+            Some(VaultError::InvalidOracle.into())
+        );
+        let mut foreign = price_update(1, 100_000_000, 0, -8, 1_000);
+        foreign[0] ^= 1;
+        assert_eq!(
+            parse(foreign, 1_000).err(),
+            Some(VaultError::InvalidOracle.into())
+        );
+        assert_eq!(
+            parse(PRICE_UPDATE_V2_DISCRIMINATOR.to_vec(), 1_000).err(),
+            Some(VaultError::InvalidOracle.into())
+        );
+    }
+}
@@ -0,0 +1,530 @@
+// anchor 0.31's generated IDL handlers still call the deprecated AccountInfo::realloc
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_hook::TransferHookAccount, BaseStateWithExtensions, StateWithExtensions},
+};
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use reza_vault::program::RezaVault;
+use reza_vault::{Position, RewardPool, VaultState};
+use spl_discriminator::SplDiscriminate;
+use spl_tlv_account_resolution::{
+    account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+declare_id!("5d9592JRYbS325CZt2oN6Z1ryg3atUNpTvMkcYavXyrD");
+
+/// Token-2022 transfer hook for reza-vault share mints.
+///
+/// On every share transfer it checks that sender and receiver are on the
+/// vault's allowlist, refuses to move shares that are still locked in the
+/// sender's `Position`, and CPIs `sync_transfer_checkpoints` so reward
+/// checkpoints and cost basis follow the shares.
+#[program]
+pub mod share_hook {
+    use super::*;
+
+    // ─────────────────────────────────────────
+    // INITIALISE ─ config + extra-account list for one share mint
+    // ─────────────────────────────────────────
+    pub fn initialize_hook(ctx: Context<InitializeHook>) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        config.vault = ctx.accounts.vault_state.key();
+        config.mint = ctx.accounts.mint.key();
+        config.reward_pools = Vec::new();
+        config.bump = ctx.bumps.hook_config;
+
+        let metas = extra_account_metas(config)?;
+        let mut data = ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &metas)?;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: allowlist management
+    // ─────────────────────────────────────────
+    pub fn allow_wallet(ctx: Context<AllowWallet>, wallet: Pubkey) -> Result<()> {
+        let entry = &mut ctx.accounts.allowlist_entry;
+        entry.mint = ctx.accounts.hook_config.mint;
+        entry.wallet = wallet;
+        Ok(())
+    }
+
+    pub fn disallow_wallet(_ctx: Context<DisallowWallet>, _wallet: Pubkey) -> Result<()> {
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // ADMIN: have transfers keep a reward pool's checkpoints in sync
+    // ─────────────────────────────────────────
+    pub fn add_reward_pool(ctx: Context<AddRewardPool>) -> Result<()> {
+        let config = &mut ctx.accounts.hook_config;
+        let pool = ctx.accounts.reward_pool.key();
+        require!(
+            !config.reward_pools.contains(&pool),
+            HookError::RewardPoolAlreadyAdded
+        );
+        require!(
+            config.reward_pools.len() < MAX_REWARD_POOLS,
+            HookError::TooManyRewardPools
+        );
+        config.reward_pools.push(pool);
+
+        let metas = extra_account_metas(config)?;
+        let mut data = ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?;
+        ExtraAccountMetaList::update::<ExecuteInstruction>(&mut data, &metas)?;
+        Ok(())
+    }
+
+    // ─────────────────────────────────────────
+    // EXECUTE ─ invoked by Token-2022 on every share transfer
+    // ─────────────────────────────────────────
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn execute<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Execute<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        // only Token-2022 mid-transfer may call in, `amount` is trusted below
+        assert_is_transferring(&ctx.accounts.source_token.to_account_info())?;
+
+        // ╭──────────────────────────────────╮
+        // │  Allowlist                       │
+        // ╰──────────────────────────────────╯
+        require!(
+            is_allowlisted(&ctx.accounts.sender_allowlist_entry),
+            HookError::SenderNotAllowed
+        );
+        require!(
+            is_allowlisted(&ctx.accounts.receiver_allowlist_entry),
+            HookError::ReceiverNotAllowed
+        );
+
+        // ╭──────────────────────────────────╮
+        // │  Lockup: locked shares stay put  │
+        // ╰──────────────────────────────────╯
+        let position_info = &ctx.accounts.sender_position;
+        if position_info.owner == &reza_vault::ID && !position_info.data_is_empty() {
+            let position = Position::try_deserialize(&mut &position_info.data.borrow()[..])?;
+            if Clock::get()?.unix_timestamp < position.unlock_ts {
+                require!(
+                    ctx.accounts.source_token.amount >= position.locked_shares,
+                    HookError::SharesLocked
+                );
+            }
+        }
+
+        // ╭──────────────────────────────────╮
+        // │  Reward checkpoints, cost basis  │
+        // ╰──────────────────────────────────╯
+        // always called: the vault rejects the transfer unless every pool is tracked
+        let hook_authority_seeds: &[&[u8]] = &[b"hook_authority", &[ctx.bumps.hook_authority]];
+        reza_vault::cpi::sync_transfer_checkpoints(
+            CpiContext::new_with_signer(
+                ctx.accounts.vault_program.to_account_info(),
+                reza_vault::cpi::accounts::SyncTransferCheckpoints {
+                    vault_state: ctx.accounts.vault_state.to_account_info(),
+                    hook_authority: ctx.accounts.hook_authority.to_account_info(),
+                    source_share_account: ctx.accounts.source_token.to_account_info(),
+                    destination_share_account: ctx.accounts.destination_token.to_account_info(),
+                    source_user_position: ctx.accounts.sender_user_position.to_account_info(),
+                    destination_user_position: ctx
+                        .accounts
+                        .receiver_user_position
+                        .to_account_info(),
+                },
+                &[hook_authority_seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            amount,
+        )?;
+        Ok(())
+    }
+}
+
+// ╭────────────────────────────────────────────
+// │              HELPERS                       │
+// ╰────────────────────────────────────────────
+/// Capacity of `HookConfig::reward_pools`, every pool the vault can run.
+pub const MAX_REWARD_POOLS: usize = reza_vault::MAX_REWARD_POOLS;
+/// Extra accounts before the per-pool triples, see `extra_account_metas`.
+const FIXED_EXTRA_ACCOUNTS: usize = 9;
+
+// Execute account indices: 0 source, 1 mint, 2 destination, 3 owner,
+// 4 extra-account-meta list, then the extra accounts below from 5 on.
+const SOURCE_INDEX: u8 = 0;
+const MINT_INDEX: u8 = 1;
+const DESTINATION_INDEX: u8 = 2;
+const VAULT_PROGRAM_INDEX: u8 = 6;
+const VAULT_STATE_INDEX: u8 = 7;
+const FIRST_POOL_INDEX: u8 = 14;
+/// Offset of `owner` in SPL token account data.
+const TOKEN_OWNER_OFFSET: u8 = 32;
+
+fn owner_of(account_index: u8) -> Seed {
+    Seed::AccountData {
+        account_index,
+        data_index: TOKEN_OWNER_OFFSET,
+        length: 32,
+    }
+}
+
+/// Accounts Token-2022 appends to `execute`, in `Execute` order, followed by
+/// a `[pool, source_checkpoint, destination_checkpoint]` triple per pool.
+fn extra_account_metas(config: &HookConfig) -> Result<Vec<ExtraAccountMeta>> {
+    let mut metas = vec![
+        // 5: hook_config
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"hook_config".to_vec(),
+                },
+                Seed::AccountKey { index: MINT_INDEX },
+            ],
+            false,
+            false,
+        )?,
+        // 6: vault_program
+        ExtraAccountMeta::new_with_pubkey(&reza_vault::ID, false, false)?,
+        // 7: vault_state
+        ExtraAccountMeta::new_with_pubkey(&config.vault, false, false)?,
+        // 8 / 9: sender / receiver allowlist entries
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"allowlist".to_vec(),
+                },
+                Seed::AccountKey { index: MINT_INDEX },
+                owner_of(SOURCE_INDEX),
+            ],
+            false,
+            false,
+        )?,
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"allowlist".to_vec(),
+                },
+                Seed::AccountKey { index: MINT_INDEX },
+                owner_of(DESTINATION_INDEX),
+            ],
+            false,
+            false,
+        )?,
+        // 10: sender_position (reza-vault PDA)
+        ExtraAccountMeta::new_external_pda_with_seeds(
+            VAULT_PROGRAM_INDEX,
+            &[
+                Seed::Literal {
+                    bytes: b"position".to_vec(),
+                },
+                Seed::AccountKey {
+                    index: VAULT_STATE_INDEX,
+                },
+                owner_of(SOURCE_INDEX),
+            ],
+            false,
+            false,
+        )?,
+        // 11: hook_authority
+        ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal {
+                bytes: b"hook_authority".to_vec(),
+            }],
+            false,
+            false,
+        )?,
+    ];
+    // 12 / 13: sender / receiver UserPosition (reza-vault PDAs)
+    for holder in [SOURCE_INDEX, DESTINATION_INDEX] {
+        metas.push(ExtraAccountMeta::new_external_pda_with_seeds(
+            VAULT_PROGRAM_INDEX,
+            &[
+                Seed::Literal {
+                    bytes: b"user_position".to_vec(),
+                },
+                Seed::AccountKey {
+                    index: VAULT_STATE_INDEX,
+                },
+                owner_of(holder),
+            ],
+            false,
+            true,
+        )?);
+    }
+    debug_assert_eq!(metas.len(), FIXED_EXTRA_ACCOUNTS);
+
+    for (i, pool) in config.reward_pools.iter().enumerate() {
+        let pool_index = FIRST_POOL_INDEX + 3 * i as u8;
+        metas.push(ExtraAccountMeta::new_with_pubkey(pool, false, true)?);
+        for holder in [SOURCE_INDEX, DESTINATION_INDEX] {
+            metas.push(ExtraAccountMeta::new_external_pda_with_seeds(
+                VAULT_PROGRAM_INDEX,
+                &[
+                    Seed::Literal {
+                        bytes: b"reward_checkpoint".to_vec(),
+                    },
+                    Seed::AccountKey { index: pool_index },
+                    owner_of(holder),
+                ],
+                false,
+                true,
+            )?);
+        }
+    }
+    Ok(metas)
+}
+
+fn assert_is_transferring(source: &AccountInfo) -> Result<()> {
+    let data = source.try_borrow_data()?;
+    let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+    let extension = account.get_extension::<TransferHookAccount>()?;
+    require!(
+        bool::from(extension.transferring),
+        HookError::NotTransferring
+    );
+    Ok(())
+}
+
+/// The entry PDA is only ever created by `allow_wallet`.
+fn is_allowlisted(entry: &AccountInfo) -> bool {
+    entry.owner == &crate::ID && !entry.data_is_empty()
+}
+
+// ╭────────────────────────────────────────────
+// │              STATE                         │
+// ╰────────────────────────────────────────────
+#[account]
+pub struct HookConfig {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub reward_pools: Vec<Pubkey>,
+    pub bump: u8,
+}
+const _HOOK_CONFIG_SIZE: usize = 8 + 32 * 2 + 4 + 32 * MAX_REWARD_POOLS + 1;
+
+/// Presence of this PDA puts `wallet` on the allowlist of `mint`.
+#[account]
+pub struct AllowlistEntry {
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+}
+const _ALLOWLIST_ENTRY_SIZE: usize = 8 + 32 * 2;
+
+#[error_code]
+pub enum HookError {
+    #[msg("Hook may only be invoked by Token-2022 during a transfer.")]
+    NotTransferring,
+    #[msg("Sender is not on the vault allowlist.")]
+    SenderNotAllowed,
+    #[msg("Receiver is not on the vault allowlist.")]
+    ReceiverNotAllowed,
+    #[msg("Transfer would move shares that are still locked.")]
+    SharesLocked,
+    #[msg("Reward pool is already tracked by the hook.")]
+    RewardPoolAlreadyAdded,
+    #[msg("Hook tracks the maximum number of reward pools.")]
+    TooManyRewardPools,
+    #[msg("Vault does not use this program as its share transfer hook.")]
+    HookNotConfigured,
+    #[msg("Reward pool belongs to another vault.")]
+    InvalidRewardPool,
+}
+
+// ╭────────────────────────────────────────────
+// │              CONTEXTS                      │
+// ╰────────────────────────────────────────────
+#[derive(Accounts)]
+pub struct InitializeHook<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"hook_config", mint.key().as_ref()],
+        bump,
+        space = _HOOK_CONFIG_SIZE
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+
+    /// CHECK: ExtraAccountMetaList, sized for every reward pool up front
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump,
+        space = ExtraAccountMetaList::size_of(FIXED_EXTRA_ACCOUNTS + 3 * MAX_REWARD_POOLS)?
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    #[account(
+        has_one = admin,
+        constraint = vault_state.share_mint == mint.key() @ HookError::HookNotConfigured,
+        constraint = vault_state.transfer_hook_program == crate::ID @ HookError::HookNotConfigured
+    )]
+    pub vault_state: Box<Account<'info, VaultState>>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AllowWallet<'info> {
+    #[account(
+        seeds = [b"hook_config", hook_config.mint.as_ref()],
+        bump = hook_config.bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+
+    #[account(address = hook_config.vault, has_one = admin)]
+    pub vault_state: Box<Account<'info, VaultState>>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"allowlist", hook_config.mint.as_ref(), wallet.as_ref()],
+        bump,
+        space = _ALLOWLIST_ENTRY_SIZE
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct DisallowWallet<'info> {
+    #[account(
+        seeds = [b"hook_config", hook_config.mint.as_ref()],
+        bump = hook_config.bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+
+    #[account(address = hook_config.vault, has_one = admin)]
+    pub vault_state: Box<Account<'info, VaultState>>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"allowlist", hook_config.mint.as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddRewardPool<'info> {
+    #[account(
+        mut,
+        seeds = [b"hook_config", hook_config.mint.as_ref()],
+        bump = hook_config.bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+
+    /// CHECK: ExtraAccountMetaList of hook_config.mint
+    #[account(
+        mut,
+        seeds = [b"extra-account-metas", hook_config.mint.as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    #[account(address = hook_config.vault, has_one = admin)]
+    pub vault_state: Box<Account<'info, VaultState>>,
+
+    #[account(
+        constraint = reward_pool.vault == vault_state.key() @ HookError::InvalidRewardPool
+    )]
+    pub reward_pool: Account<'info, RewardPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Execute<'info> {
+    #[account(token::mint = mint)]
+    pub source_token: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(token::mint = mint)]
+    pub destination_token: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: source owner or delegate, already authorised by Token-2022
+    pub owner: UncheckedAccount<'info>,
+
+    /// CHECK: ExtraAccountMetaList of the mint
+    #[account(seeds = [b"extra-account-metas", mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"hook_config", mint.key().as_ref()], bump = hook_config.bump)]
+    pub hook_config: Account<'info, HookConfig>,
+
+    pub vault_program: Program<'info, RezaVault>,
+
+    #[account(address = hook_config.vault)]
+    pub vault_state: Box<Account<'info, VaultState>>,
+
+    /// CHECK: allowlist PDA of the sender, may not exist
+    #[account(
+        seeds = [b"allowlist", mint.key().as_ref(), source_token.owner.as_ref()],
+        bump
+    )]
+    pub sender_allowlist_entry: UncheckedAccount<'info>,
+
+    /// CHECK: allowlist PDA of the receiver, may not exist
+    #[account(
+        seeds = [b"allowlist", mint.key().as_ref(), destination_token.owner.as_ref()],
+        bump
+    )]
+    pub receiver_allowlist_entry: UncheckedAccount<'info>,
+
+    /// CHECK: sender's reza-vault Position, may not exist
+    #[account(
+        seeds = [b"position", vault_state.key().as_ref(), source_token.owner.as_ref()],
+        bump,
+        seeds::program = vault_program.key()
+    )]
+    pub sender_position: UncheckedAccount<'info>,
+
+    /// CHECK: PDA signer for `sync_transfer_checkpoints`
+    #[account(seeds = [b"hook_authority"], bump)]
+    pub hook_authority: UncheckedAccount<'info>,
+
+    /// CHECK: sender's reza-vault UserPosition, may not exist
+    #[account(
+        mut,
+        seeds = [b"user_position", vault_state.key().as_ref(), source_token.owner.as_ref()],
+        bump,
+        seeds::program = vault_program.key()
+    )]
+    pub sender_user_position: UncheckedAccount<'info>,
+
+    /// CHECK: receiver's reza-vault UserPosition, may not exist
+    #[account(
+        mut,
+        seeds = [b"user_position", vault_state.key().as_ref(), destination_token.owner.as_ref()],
+        bump,
+        seeds::program = vault_program.key()
+    )]
+    pub receiver_user_position: UncheckedAccount<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn vault_pins_this_program() {
+        assert_eq!(reza_vault::SHARE_HOOK_PROGRAM_ID, crate::ID);
+    }
+}
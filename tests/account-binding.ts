@@ -0,0 +1,100 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+    TOKEN_2022_PROGRAM_ID,
+    createMint,
+    getOrCreateAssociatedTokenAccount,
+    mintTo,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    DECIMALS,
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    getTokenBalance,
+} from "./helpers";
+
+describe("vault account binding", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let victim: UserFixture;
+    let attacker: UserFixture;
+    let fakeMint: anchor.web3.PublicKey;
+    let fakeShareAccount: anchor.web3.PublicKey;
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        victim = await setupUser(provider, vault, 100 * UNIT);
+        attacker = await setupUser(provider, vault, 0);
+        await deposit(program, vault, victim, 100 * UNIT);
+
+        // a Token-2022 mint the attacker controls, with "shares" to burn
+        const payer = (provider.wallet as any).payer;
+        fakeMint = await createMint(
+            connection,
+            payer,
+            attacker.keypair.publicKey,
+            null,
+            DECIMALS,
+            undefined,
+            undefined,
+            TOKEN_2022_PROGRAM_ID
+        );
+        fakeShareAccount = (
+            await getOrCreateAssociatedTokenAccount(
+                connection,
+                payer,
+                fakeMint,
+                attacker.keypair.publicKey,
+                false,
+                undefined,
+                undefined,
+                TOKEN_2022_PROGRAM_ID
+            )
+        ).address;
+        await mintTo(
+            connection,
+            payer,
+            fakeMint,
+            fakeShareAccount,
+            attacker.keypair,
+            100 * UNIT,
+            [],
+            undefined,
+            TOKEN_2022_PROGRAM_ID
+        );
+    });
+
+    it("rejects withdrawals that burn a foreign share mint", async () => {
+        try {
+            await withdraw(program, vault, attacker, 100 * UNIT, {
+                shareMint: fakeMint,
+                userShareAccount: fakeShareAccount,
+            });
+            assert.fail("paid out against a foreign share mint");
+        } catch (err: any) {
+            assert.include(err.toString(), "ConstraintHasOne");
+        }
+        assert.strictEqual(await getTokenBalance(connection, vault.vaultAssetAccount), 100 * UNIT);
+    });
+
+    it("rejects a share account of another mint", async () => {
+        try {
+            await withdraw(program, vault, attacker, 100 * UNIT, { userShareAccount: fakeShareAccount });
+            assert.fail("burned from a share account of another mint");
+        } catch (err: any) {
+            assert.include(err.toString(), "ConstraintTokenMint");
+        }
+        assert.strictEqual(await getTokenBalance(connection, vault.vaultAssetAccount), 100 * UNIT);
+    });
+});
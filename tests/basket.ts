@@ -0,0 +1,302 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+    TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    createMint,
+    getOrCreateAssociatedTokenAccount,
+    mintTo,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import { MockOracle } from "../target/types/mock_oracle";
+import {
+    DECIMALS,
+    UNIT,
+    getTokenBalance,
+    prepareProtocol,
+    protocolConfigPda,
+} from "./helpers";
+
+describe("basket vault", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+    const oracle = anchor.workspace.MockOracle as Program<MockOracle>;
+    const payer = (provider.wallet as any).payer;
+    const USD = 10 ** 6;
+
+    type Constituent = {
+        mint: anchor.web3.PublicKey;
+        oracle: anchor.web3.Keypair;
+        basketAccount: anchor.web3.PublicKey;
+        userAccount: anchor.web3.PublicKey;
+    };
+
+    const user = anchor.web3.Keypair.generate();
+    const constituents: Constituent[] = [];
+    let basket: anchor.web3.PublicKey;
+    let basketAuthority: anchor.web3.PublicKey;
+    let shareMint: anchor.web3.PublicKey;
+    let userShareAccount: anchor.web3.PublicKey;
+    let vaultCount: number;
+
+    const setPrice = (constituent: Constituent, usd: number) =>
+        oracle.methods
+            .setPrice(
+                new anchor.BN(usd * 10 ** 8),
+                new anchor.BN(usd * 10 ** 4), // 0.01 %
+                -8,
+                new anchor.BN(Math.floor(Date.now() / 1000))
+            )
+            .accounts({
+                priceUpdate: constituent.oracle.publicKey,
+                writeAuthority: provider.wallet.publicKey,
+            } as any)
+            .rpc();
+
+    const oracleAccounts = () =>
+        constituents.map((constituent) => ({
+            pubkey: constituent.oracle.publicKey,
+            isWritable: false,
+            isSigner: false,
+        }));
+
+    const basketNav = async () =>
+        (await program.methods
+            .getBasketNav()
+            .accounts({ basket } as any)
+            .remainingAccounts(oracleAccounts())
+            .view()) as any;
+
+    const setBasketPause = (pause: boolean) =>
+        program.methods
+            .setBasketPause(pause)
+            .accounts({ basket, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+    const basketDeposit = (constituent: Constituent, amount: number) =>
+        program.methods
+            .basketDeposit(new anchor.BN(amount), new anchor.BN(0))
+            .accounts({
+                basket,
+                basketAuthority,
+                basketAssetAccount: constituent.basketAccount,
+                userAssetAccount: constituent.userAccount,
+                shareMint,
+                receiverShareAccount: userShareAccount,
+                user: user.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            } as any)
+            .remainingAccounts(oracleAccounts())
+            .signers([user])
+            .rpc();
+
+    const basketWithdraw = (constituent: Constituent, shares: number) =>
+        program.methods
+            .basketWithdraw(new anchor.BN(shares), new anchor.BN(0))
+            .accounts({
+                basket,
+                basketAuthority,
+                basketAssetAccount: constituent.basketAccount,
+                receiverAssetAccount: constituent.userAccount,
+                shareMint,
+                userShareAccount,
+                user: user.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            } as any)
+            .remainingAccounts(oracleAccounts())
+            .signers([user])
+            .rpc();
+
+    before(async () => {
+        const sig = await connection.requestAirdrop(user.publicKey, anchor.web3.LAMPORTS_PER_SOL * 2);
+        await connection.confirmTransaction(sig, "confirmed");
+
+        [basket] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("basket"), provider.wallet.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+            program.programId
+        );
+        [basketAuthority] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("basket_authority"), basket.toBuffer()],
+            program.programId
+        );
+        [shareMint] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("basket_shares"), basket.toBuffer()],
+            program.programId
+        );
+
+        const config = (await program.account.protocolConfig.fetch(protocolConfigPda(program))) as any;
+        vaultCount = config.vaultCount.toNumber();
+
+        // a deposit may lift its constituent to at most target + 30 %
+        await program.methods
+            .initializeBasket(new anchor.BN(0), new anchor.BN(600), 100, 3_000)
+            .accounts({
+                basket,
+                basketAuthority,
+                shareMint,
+                admin: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            } as any)
+            .rpc();
+        userShareAccount = (
+            await getOrCreateAssociatedTokenAccount(
+                connection,
+                payer,
+                shareMint,
+                user.publicKey,
+                false,
+                undefined,
+                undefined,
+                TOKEN_2022_PROGRAM_ID
+            )
+        ).address;
+
+        // two constituents at 50 / 50, priced $1 and $2
+        for (const usd of [1, 2]) {
+            const mint = await createMint(connection, payer, provider.wallet.publicKey, null, DECIMALS);
+            await prepareProtocol(program, provider, mint);
+
+            const priceAccount = anchor.web3.Keypair.generate();
+            await oracle.methods
+                .initializePrice([...mint.toBuffer()])
+                .accounts({
+                    priceUpdate: priceAccount.publicKey,
+                    authority: provider.wallet.publicKey,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                } as any)
+                .signers([priceAccount])
+                .rpc();
+
+            const [basketAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("basket_asset"), basket.toBuffer(), mint.toBuffer()],
+                program.programId
+            );
+            await program.methods
+                .addBasketAsset(5_000)
+                .accounts({
+                    basket,
+                    basketAuthority,
+                    assetMint: mint,
+                    basketAssetAccount: basketAccount,
+                    oracle: priceAccount.publicKey,
+                    admin: provider.wallet.publicKey,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                } as any)
+                .rpc();
+
+            const userAccount = (await getOrCreateAssociatedTokenAccount(connection, payer, mint, user.publicKey))
+                .address;
+            await mintTo(connection, payer, mint, userAccount, provider.wallet.publicKey, 1_000 * UNIT);
+
+            const constituent = { mint, oracle: priceAccount, basketAccount, userAccount };
+            constituents.push(constituent);
+            await setPrice(constituent, usd);
+        }
+    });
+
+    it("keeps the basket out of the vault registry", async () => {
+        const config = (await program.account.protocolConfig.fetch(protocolConfigPda(program))) as any;
+        assert.strictEqual(config.vaultCount.toNumber(), vaultCount);
+    });
+
+    it("prices deposits in any constituent by USD value", async () => {
+        await basketDeposit(constituents[0], 100 * UNIT); // $100
+        await basketDeposit(constituents[1], 50 * UNIT); // $100
+
+        assert.strictEqual(await getTokenBalance(connection, userShareAccount), 200 * USD);
+        const nav = await basketNav();
+        assert.strictEqual(nav.navUsd.toNumber(), 200 * USD);
+        assert.strictEqual(nav.sharePriceUsd.toNumber(), 1 * USD);
+        assert.deepEqual(nav.weightsBps, [5_000, 5_000]);
+        assert.deepEqual(nav.targetWeightsBps, [5_000, 5_000]);
+    });
+
+    it("pays withdrawals in one constituent at the current NAV", async () => {
+        await setPrice(constituents[1], 4); // NAV $300, $1.50 a share
+
+        const before = await getTokenBalance(connection, constituents[0].userAccount);
+        await basketWithdraw(constituents[0], 30 * USD); // $100 - $45 of $255: 22 %
+
+        const after = await getTokenBalance(connection, constituents[0].userAccount);
+        assert.strictEqual(after - before, 45 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, userShareAccount), 170 * USD);
+    });
+
+    it("redeems in kind, pro rata across every constituent", async () => {
+        const shares = 50 * USD;
+        const totalShares = 170 * USD;
+        const holdings = [55 * UNIT, 50 * UNIT];
+        const before = await Promise.all(
+            constituents.map((constituent) => getTokenBalance(connection, constituent.userAccount))
+        );
+
+        await program.methods
+            .basketRedeemInKind(new anchor.BN(shares))
+            .accounts({
+                basket,
+                basketAuthority,
+                shareMint,
+                userShareAccount,
+                user: user.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            } as any)
+            .remainingAccounts(
+                constituents.flatMap((constituent) => [
+                    { pubkey: constituent.basketAccount, isWritable: true, isSigner: false },
+                    { pubkey: constituent.userAccount, isWritable: true, isSigner: false },
+                ])
+            )
+            .signers([user])
+            .rpc();
+
+        for (const [i, constituent] of constituents.entries()) {
+            const received = (await getTokenBalance(connection, constituent.userAccount)) - before[i];
+            assert.strictEqual(received, Math.floor((holdings[i] * shares) / totalShares));
+        }
+        assert.strictEqual(await getTokenBalance(connection, userShareAccount), 120 * USD);
+    });
+
+    it("rejects deposits that lift a constituent past its weight band", async () => {
+        // about $39 of the $1 mint and $141 of the $4 mint: 78 % in the second
+        try {
+            await basketDeposit(constituents[1], 10 * UNIT); // +$40 ⇒ 82 %
+            assert.fail("deposit overweighted a constituent");
+        } catch (err: any) {
+            assert.include(err.toString(), "BasketWeightExceeded");
+        }
+        // the underweight constituent is still welcome
+        await basketDeposit(constituents[0], 10 * UNIT);
+    });
+
+    it("rejects withdrawals that drop a constituent below its weight band", async () => {
+        // about $49 of the $1 mint in a $190 basket at $1.50 a share: 26 %
+        try {
+            await basketWithdraw(constituents[0], 10 * USD); // -$15 ⇒ 19 %
+            assert.fail("withdrawal underweighted a constituent");
+        } catch (err: any) {
+            assert.include(err.toString(), "BasketWeightExceeded");
+        }
+        // the overweight constituent can still be drawn down
+        await basketWithdraw(constituents[1], 10 * USD);
+    });
+
+    it("stops deposits while the basket is paused", async () => {
+        await setBasketPause(true);
+        try {
+            await basketDeposit(constituents[0], 10 * UNIT);
+            assert.fail("deposit into a paused basket");
+        } catch (err: any) {
+            assert.include(err.toString(), "VaultPaused");
+        } finally {
+            await setBasketPause(false);
+        }
+    });
+});
@@ -0,0 +1,156 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    getTokenBalance,
+} from "./helpers";
+
+describe("batch deposit", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let treasury: UserFixture;
+    let team: UserFixture[];
+
+    const pda = (seed: string, target: VaultFixture, owner: UserFixture) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from(seed), target.vaultState.toBuffer(), owner.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const writable = (pubkey: anchor.web3.PublicKey) => ({ pubkey, isWritable: true, isSigner: false });
+    const none = { pubkey: program.programId, isWritable: false, isSigner: false };
+
+    /**
+     * `[share_account, position, user_position]` per recipient; the program
+     * id leaves a PDA out.
+     */
+    const batchDeposit = (
+        recipients: UserFixture[],
+        amounts: number[],
+        { target = vault, funder = treasury, positions = false, userPositions = false } = {}
+    ) =>
+        program.methods
+            .batchDeposit(amounts.map((amount) => new anchor.BN(amount)))
+            .accounts({
+                vaultState: target.vaultState,
+                vaultAssetAccount: target.vaultAssetAccount,
+                userAssetAccount: funder.assetAccount,
+                shareMint: target.shareMint,
+                vaultAuthority: target.vaultAuthority,
+                user: funder.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .remainingAccounts(
+                recipients.flatMap((member) => [
+                    writable(member.shareAccount),
+                    positions ? writable(pda("position", target, member)) : none,
+                    userPositions ? writable(pda("user_position", target, member)) : none,
+                ])
+            )
+            .signers([funder.keypair])
+            .rpc();
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        treasury = await setupUser(provider, vault, 1_000 * UNIT);
+        team = [];
+        for (let i = 0; i < 3; i++) {
+            team.push(await setupUser(provider, vault, 0));
+        }
+    });
+
+    it("mints to every recipient from one funding source", async () => {
+        const txSig = await batchDeposit(team, [10 * UNIT, 20 * UNIT, 30 * UNIT]);
+
+        for (const [i, member] of team.entries()) {
+            assert.strictEqual(await getTokenBalance(connection, member.shareAccount), (i + 1) * 10 * UNIT);
+        }
+        assert.strictEqual(await getTokenBalance(connection, treasury.assetAccount), 940 * UNIT);
+
+        const state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.strictEqual(state.totalAsset.toNumber(), 60 * UNIT);
+        assert.strictEqual(state.totalShares.toNumber(), 60 * UNIT);
+
+        // one DepositEvent per recipient
+        await connection.confirmTransaction(txSig, "confirmed");
+        const tx = await connection.getTransaction(txSig, {
+            commitment: "confirmed",
+            maxSupportedTransactionVersion: 0,
+        });
+        const parser = new anchor.EventParser(program.programId, program.coder);
+        const receivers = [...parser.parseLogs(tx!.meta!.logMessages!)]
+            .filter((event) => event.name === "depositEvent")
+            .map((event) => (event.data as any).receiver.toBase58());
+        assert.deepEqual(
+            receivers,
+            team.map((member) => member.keypair.publicKey.toBase58())
+        );
+    });
+
+    it("books cost basis only for the funder's own share of the batch", async () => {
+        await batchDeposit([treasury, team[0]], [10 * UNIT, 20 * UNIT], { userPositions: true });
+
+        const own = (await program.account.userPosition.fetch(
+            pda("user_position", vault, treasury)
+        )) as any;
+        assert.strictEqual(own.totalDeposited.toNumber(), 10 * UNIT);
+        assert.strictEqual(own.costBasis.toNumber(), 10 * UNIT);
+
+        // created for the recipient, but paid for by someone else
+        const gifted = (await program.account.userPosition.fetch(
+            pda("user_position", vault, team[0])
+        )) as any;
+        assert.isTrue(gifted.owner.equals(team[0].keypair.publicKey));
+        assert.strictEqual(gifted.costBasis.toNumber(), 0);
+    });
+
+    it("locks the new shares of every recipient on a vault with a lockup", async () => {
+        const locked = await setupVault(program, provider, { nonTransferable: true });
+        const funder = await setupUser(provider, locked, 1_000 * UNIT);
+        const member = await setupUser(provider, locked, 0);
+        await program.methods
+            .setLockup(new anchor.BN(3_600), 0)
+            .accounts({ vaultState: locked.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+        try {
+            await batchDeposit([member], [10 * UNIT], { target: locked, funder });
+            assert.fail("minted locked shares without a position");
+        } catch (err: any) {
+            assert.include(err.toString(), "PositionRequired");
+        }
+
+        await batchDeposit([funder, member], [10 * UNIT, 20 * UNIT], {
+            target: locked,
+            funder,
+            positions: true,
+        });
+        const own = (await program.account.position.fetch(pda("position", locked, funder))) as any;
+        const gifted = (await program.account.position.fetch(pda("position", locked, member))) as any;
+        assert.strictEqual(own.lockedShares.toNumber(), 10 * UNIT);
+        assert.strictEqual(gifted.lockedShares.toNumber(), 20 * UNIT);
+        assert.isAbove(gifted.unlockTs.toNumber(), 0);
+    });
+
+    it("needs one recipient per amount", async () => {
+        try {
+            await batchDeposit(team.slice(0, 2), [10 * UNIT, 20 * UNIT, 30 * UNIT]);
+            assert.fail("accepted a short recipient list");
+        } catch (err: any) {
+            assert.include(err.toString(), "BatchLengthMismatch");
+        }
+    });
+});
@@ -0,0 +1,203 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+    TOKEN_PROGRAM_ID,
+    createMint,
+    getOrCreateAssociatedTokenAccount,
+    mintTo,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import { MockAmm } from "../target/types/mock_amm";
+import { MockOracle } from "../target/types/mock_oracle";
+import {
+    DECIMALS,
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    getTokenBalance,
+} from "./helpers";
+
+describe("compound", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+    const amm = anchor.workspace.MockAmm as Program<MockAmm>;
+    const oracle = anchor.workspace.MockOracle as Program<MockOracle>;
+    const payer = (provider.wallet as any).payer;
+
+    let vault: VaultFixture;
+    let user: UserFixture;
+    let rewardMint: anchor.web3.PublicKey;
+    let harvestAccount: anchor.web3.PublicKey;
+    let harvestConfig: anchor.web3.PublicKey;
+    let swapAuthority: anchor.web3.PublicKey;
+    let pool: anchor.web3.PublicKey;
+    let poolVaultIn: anchor.web3.PublicKey;
+    let poolVaultOut: anchor.web3.PublicKey;
+    let keeperAssetAccount: anchor.web3.PublicKey;
+    const assetOracle = anchor.web3.Keypair.generate();
+    const rewardOracle = anchor.web3.Keypair.generate();
+
+    const now = () => Math.floor(Date.now() / 1000);
+
+    const setPrice = (priceUpdate: anchor.web3.Keypair, usd: number) => {
+        const price = usd * 10 ** 8;
+        return oracle.methods
+            .setPrice(new anchor.BN(price), new anchor.BN(price / 10_000), -8, new anchor.BN(now()))
+            .accounts({ priceUpdate: priceUpdate.publicKey, writeAuthority: provider.wallet.publicKey } as any)
+            .rpc();
+    };
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        user = await setupUser(provider, vault, 1_000 * UNIT);
+        await deposit(program, vault, user, 100 * UNIT);
+
+        rewardMint = await createMint(connection, payer, provider.wallet.publicKey, null, DECIMALS);
+        [harvestAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("harvest"), vault.vaultState.toBuffer(), rewardMint.toBuffer()],
+            program.programId
+        );
+        [harvestConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("harvest_config"), vault.vaultState.toBuffer(), rewardMint.toBuffer()],
+            program.programId
+        );
+        [swapAuthority] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("swap_authority"), vault.vaultState.toBuffer()],
+            program.programId
+        );
+
+        // Asset A at $1, the reward at $2
+        for (const priceUpdate of [assetOracle, rewardOracle]) {
+            await oracle.methods
+                .initializePrice([...Buffer.alloc(32, 1)])
+                .accounts({
+                    priceUpdate: priceUpdate.publicKey,
+                    authority: provider.wallet.publicKey,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                } as any)
+                .signers([priceUpdate])
+                .rpc();
+        }
+        await setPrice(assetOracle, 1);
+        await setPrice(rewardOracle, 2);
+        await program.methods
+            .setOracle(assetOracle.publicKey, new anchor.BN(600), 100) // 10 min, 1 %
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+        await program.methods
+            .createHarvestAccount()
+            .accounts({
+                vaultState: vault.vaultState,
+                harvestAccount,
+                harvestConfig,
+                rewardOracle: rewardOracle.publicKey,
+                swapAuthority,
+                rewardMint,
+                admin: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            } as any)
+            .rpc();
+
+        // 1 reward token ➜ 2 Asset A
+        [pool] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("pool"), rewardMint.toBuffer(), vault.assetMint.toBuffer()],
+            amm.programId
+        );
+        [poolVaultIn] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("pool_vault"), pool.toBuffer(), rewardMint.toBuffer()],
+            amm.programId
+        );
+        [poolVaultOut] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("pool_vault"), pool.toBuffer(), vault.assetMint.toBuffer()],
+            amm.programId
+        );
+        await amm.methods
+            .initializePool(new anchor.BN(2), new anchor.BN(1))
+            .accounts({
+                pool,
+                vaultIn: poolVaultIn,
+                vaultOut: poolVaultOut,
+                mintIn: rewardMint,
+                mintOut: vault.assetMint,
+                payer: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            } as any)
+            .rpc();
+        await mintTo(connection, payer, vault.assetMint, poolVaultOut, provider.wallet.publicKey, 1_000 * UNIT);
+
+        // rewards land in the harvest account, e.g. from an external farm
+        await mintTo(connection, payer, rewardMint, harvestAccount, provider.wallet.publicKey, 10 * UNIT);
+
+        keeperAssetAccount = (
+            await getOrCreateAssociatedTokenAccount(connection, payer, vault.assetMint, provider.wallet.publicKey)
+        ).address;
+
+        await program.methods
+            .setCompoundConfig(amm.programId, 100, 100) // 1 % keeper bounty, 1 % max slippage
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+    });
+
+    const compound = () =>
+        program.methods
+            .compound()
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                harvestAccount,
+                harvestConfig,
+                assetOracle: assetOracle.publicKey,
+                rewardOracle: rewardOracle.publicKey,
+                swapAuthority,
+                vaultAuthority: vault.vaultAuthority,
+                keeperAssetAccount,
+                keeper: provider.wallet.publicKey,
+                swapProgram: amm.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            } as any)
+            .remainingAccounts([
+                { pubkey: pool, isWritable: false, isSigner: false },
+                { pubkey: poolVaultIn, isWritable: true, isSigner: false },
+                { pubkey: poolVaultOut, isWritable: true, isSigner: false },
+            ])
+            .rpc();
+
+    it("rejects a swap filling below the oracle quote", async () => {
+        // the oracle now quotes 10 rewards at 25 Asset A, the pool still pays 20
+        await setPrice(rewardOracle, 2.5);
+        try {
+            await compound();
+            assert.fail("slippage check did not fire");
+        } catch (err: any) {
+            assert.include(err.toString(), "SlippageExceeded");
+        }
+        await setPrice(rewardOracle, 2);
+    });
+
+    it("swaps harvested rewards into total_asset and pays the keeper", async () => {
+        const keeperBefore = await getTokenBalance(connection, keeperAssetAccount);
+
+        await compound();
+
+        // 10 rewards ➜ 20 Asset A, 1 % (0.2) to the keeper
+        const state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.strictEqual(state.totalAsset.toNumber(), 100 * UNIT + 19.8 * UNIT);
+        assert.strictEqual(state.totalShares.toNumber(), 100 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, harvestAccount), 0);
+        assert.strictEqual(
+            (await getTokenBalance(connection, keeperAssetAccount)) - keeperBefore,
+            0.2 * UNIT
+        );
+    });
+});
@@ -0,0 +1,105 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    protocolConfigPda,
+} from "./helpers";
+
+describe("user position accounting", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let alice: UserFixture;
+    let borrower: UserFixture;
+    let stranger: UserFixture;
+
+    const userPositionPda = (user: UserFixture) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("user_position"), vault.vaultState.toBuffer(), user.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const readPosition = (user: UserFixture) =>
+        program.methods
+            .getUserPosition()
+            .accounts({ userPosition: userPositionPda(user) } as any)
+            .view();
+
+    // a flash loan with a 10 % fee lifts the share price by 10 % of the loan
+    const raiseSharePrice = async (loan: number) => {
+        const accounts = {
+            vaultState: vault.vaultState,
+            vaultAssetAccount: vault.vaultAssetAccount,
+            borrowerAssetAccount: borrower.assetAccount,
+            protocolConfig: protocolConfigPda(program),
+            borrower: borrower.keypair.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        };
+        const borrowIx = await program.methods
+            .flashBorrow(new anchor.BN(loan))
+            .accounts({ ...accounts, instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY } as any)
+            .instruction();
+        const repayIx = await program.methods.flashRepay().accounts(accounts as any).instruction();
+        await provider.sendAndConfirm(new anchor.web3.Transaction().add(borrowIx, repayIx), [
+            borrower.keypair,
+        ]);
+    };
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        alice = await setupUser(provider, vault, 1_000 * UNIT);
+        borrower = await setupUser(provider, vault, 10 * UNIT);
+        stranger = await setupUser(provider, vault, 10 * UNIT);
+
+        await program.methods
+            .setFlashLoanFee(1_000)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+    });
+
+    it("records deposits at cost", async () => {
+        await deposit(program, vault, alice, 100 * UNIT, { userPosition: userPositionPda(alice) });
+
+        const position = (await readPosition(alice)) as any;
+        assert.strictEqual(position.totalDeposited.toNumber(), 100 * UNIT);
+        assert.strictEqual(position.shares.toNumber(), 100 * UNIT);
+        assert.strictEqual(position.avgCostPerShare.toNumber(), 1_000_000_000); // 1.0
+        assert.strictEqual(position.realizedPnl.toNumber(), 0);
+    });
+
+    it("realizes PnL against the average cost on withdrawal", async () => {
+        await raiseSharePrice(100 * UNIT); // 110 assets / 100 shares
+
+        await withdraw(program, vault, alice, 50 * UNIT, { userPosition: userPositionPda(alice) });
+
+        const position = (await readPosition(alice)) as any;
+        assert.strictEqual(position.totalWithdrawn.toNumber(), 55 * UNIT);
+        assert.strictEqual(position.shares.toNumber(), 50 * UNIT);
+        assert.strictEqual(position.costBasis.toNumber(), 50 * UNIT);
+        assert.strictEqual(position.realizedPnl.toNumber(), 5 * UNIT);
+    });
+
+    it("leaves the cost basis alone on deposits paid by someone else", async () => {
+        await deposit(program, vault, stranger, 1 * UNIT, {
+            receiver: alice.keypair.publicKey,
+            receiverShareAccount: alice.shareAccount,
+            userPosition: userPositionPda(alice),
+        });
+
+        const position = (await readPosition(alice)) as any;
+        assert.strictEqual(position.totalDeposited.toNumber(), 100 * UNIT);
+        assert.strictEqual(position.shares.toNumber(), 50 * UNIT);
+        assert.strictEqual(position.costBasis.toNumber(), 50 * UNIT);
+    });
+});
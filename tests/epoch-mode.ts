@@ -0,0 +1,218 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    getTokenBalance,
+    protocolConfigPda,
+    sleep,
+} from "./helpers";
+
+describe("epoch mode", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let alice: UserFixture;
+    let bob: UserFixture;
+
+    const requestPda = (user: anchor.web3.PublicKey) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("epoch_request"), vault.vaultState.toBuffer(), user.toBuffer()],
+            program.programId
+        )[0];
+
+    const settlementPda = (epoch: number) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("epoch"),
+                vault.vaultState.toBuffer(),
+                new anchor.BN(epoch).toArrayLike(Buffer, "le", 8),
+            ],
+            program.programId
+        )[0];
+
+    const requestDeposit = (user: UserFixture, amount: number) =>
+        program.methods
+            .requestDeposit(new anchor.BN(amount))
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                userAssetAccount: user.assetAccount,
+                epochRequest: requestPda(user.keypair.publicKey),
+                protocolConfig: protocolConfigPda(program),
+                user: user.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([user.keypair])
+            .rpc();
+
+    const requestRedeem = (user: UserFixture, shares: number) =>
+        program.methods
+            .requestRedeem(new anchor.BN(shares))
+            .accounts({
+                vaultState: vault.vaultState,
+                userShareAccount: user.shareAccount,
+                shareMint: vault.shareMint,
+                epochRequest: requestPda(user.keypair.publicKey),
+                position: null,
+                protocolConfig: protocolConfigPda(program),
+                user: user.keypair.publicKey,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([user.keypair])
+            .rpc();
+
+    const settle = (epoch: number) =>
+        program.methods
+            .settleEpoch()
+            .accounts({
+                vaultState: vault.vaultState,
+                epochSettlement: settlementPda(epoch),
+                protocolConfig: protocolConfigPda(program),
+                cranker: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .rpc();
+
+    const userPositionPda = (user: UserFixture) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("user_position"), vault.vaultState.toBuffer(), user.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const claim = (user: UserFixture, epoch: number, extra: Record<string, anchor.web3.PublicKey | null> = {}) =>
+        program.methods
+            .claimEpoch()
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                userAssetAccount: user.assetAccount,
+                userShareAccount: user.shareAccount,
+                shareMint: vault.shareMint,
+                epochRequest: requestPda(user.keypair.publicKey),
+                epochSettlement: settlementPda(epoch),
+                vaultAuthority: vault.vaultAuthority,
+                position: null,
+                userPosition: null,
+                protocolConfig: protocolConfigPda(program),
+                user: user.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                ...extra,
+            } as any)
+            .signers([user.keypair])
+            .rpc();
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        alice = await setupUser(provider, vault, 1_000 * UNIT);
+        bob = await setupUser(provider, vault, 1_000 * UNIT);
+
+        // seed the vault with an immediate deposit before switching modes
+        await deposit(program, vault, alice, 100 * UNIT);
+
+        await program.methods
+            .setEpochMode(true, new anchor.BN(1))
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+    });
+
+    it("rejects immediate deposits while in epoch mode", async () => {
+        try {
+            await deposit(program, vault, bob, 10 * UNIT);
+            assert.fail("deposit should be blocked in epoch mode");
+        } catch (err: any) {
+            assert.include(err.toString(), "EpochModeActive");
+        }
+    });
+
+    it("settles deposits and redemptions at one price", async () => {
+        await requestDeposit(bob, 50 * UNIT);
+        await requestRedeem(alice, 40 * UNIT);
+
+        let state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.strictEqual(state.pendingDepositAssets.toNumber(), 50 * UNIT);
+        assert.strictEqual(state.pendingRedeemShares.toNumber(), 40 * UNIT);
+        // totals are untouched until settlement
+        assert.strictEqual(state.totalAsset.toNumber(), 100 * UNIT);
+        assert.strictEqual(state.totalShares.toNumber(), 100 * UNIT);
+
+        await sleep(2_000);
+        await settle(0);
+
+        state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.strictEqual(state.currentEpoch.toNumber(), 1);
+        assert.strictEqual(state.totalAsset.toNumber(), 110 * UNIT);
+        assert.strictEqual(state.totalShares.toNumber(), 110 * UNIT);
+
+        await claim(bob, 0, { userPosition: userPositionPda(bob) });
+        await claim(alice, 0);
+
+        assert.strictEqual(await getTokenBalance(connection, bob.shareAccount), 50 * UNIT);
+        const position = (await program.account.userPosition.fetch(userPositionPda(bob))) as any;
+        assert.strictEqual(position.shares.toNumber(), 50 * UNIT);
+        assert.strictEqual(position.costBasis.toNumber(), 50 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, alice.shareAccount), 60 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, alice.assetAccount), 940 * UNIT);
+    });
+
+    it("pays epoch redemptions only up to the redemption gate", async () => {
+        await program.methods
+            .setRedemptionGate(1_000, new anchor.BN(3_600), 0) // 10 % per hour
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+        await requestDeposit(alice, 10 * UNIT);
+        await requestRedeem(alice, 20 * UNIT);
+        await sleep(2_000);
+        await settle(1);
+
+        // the deposit side mints, the 20 Asset A payout is cut to the
+        // window's 10 (10 % of the settled 100) and the rest waits
+        await claim(alice, 1);
+        assert.strictEqual(await getTokenBalance(connection, alice.shareAccount), 50 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, alice.assetAccount), 940 * UNIT);
+        const request = (await program.account.epochRequest.fetch(requestPda(alice.keypair.publicKey))) as any;
+        assert.strictEqual(request.redeemShares.toNumber(), 10 * UNIT);
+
+        try {
+            await claim(alice, 1);
+            assert.fail("claimed past the redemption gate");
+        } catch (err: any) {
+            assert.include(err.toString(), "RedemptionGateExceeded");
+        }
+
+        await program.methods
+            .setRedemptionGate(0, new anchor.BN(0), 0)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+        await claim(alice, 1);
+        assert.strictEqual(await getTokenBalance(connection, alice.assetAccount), 950 * UNIT);
+    });
+
+    it("rejects settling before the epoch ends", async () => {
+        await program.methods
+            .setEpochMode(true, new anchor.BN(3_600))
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+        try {
+            await settle(2);
+            assert.fail("epoch settled early");
+        } catch (err: any) {
+            assert.include(err.toString(), "EpochNotEnded");
+        }
+    });
+});
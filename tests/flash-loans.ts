@@ -0,0 +1,111 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    getTokenBalance,
+    protocolConfigPda,
+} from "./helpers";
+
+describe("flash loans", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let depositor: UserFixture;
+    let borrower: UserFixture;
+
+    const borrowIx = (amount: number) =>
+        program.methods
+            .flashBorrow(new anchor.BN(amount))
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                borrowerAssetAccount: borrower.assetAccount,
+                borrower: borrower.keypair.publicKey,
+                instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            } as any)
+            .instruction();
+
+    const repayIx = () =>
+        program.methods
+            .flashRepay()
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                borrowerAssetAccount: borrower.assetAccount,
+                protocolConfig: protocolConfigPda(program),
+                borrower: borrower.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            } as any)
+            .instruction();
+
+    const send = (...ixs: anchor.web3.TransactionInstruction[]) =>
+        provider.sendAndConfirm(new anchor.web3.Transaction().add(...ixs), [borrower.keypair]);
+
+    const setFlashPause = (pause: boolean) =>
+        program.methods
+            .setFlashPause(pause)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        depositor = await setupUser(provider, vault, 1_000 * UNIT);
+        borrower = await setupUser(provider, vault, 10 * UNIT); // covers the fee
+        await deposit(program, vault, depositor, 1_000 * UNIT);
+
+        await program.methods
+            .setFlashLoanFee(100) // 1 %
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+    });
+
+    it("lends and takes back the loan plus fee within one transaction", async () => {
+        await send(await borrowIx(500 * UNIT), await repayIx());
+
+        assert.strictEqual(await getTokenBalance(connection, vault.vaultAssetAccount), 1_005 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, borrower.assetAccount), 5 * UNIT);
+
+        // the fee is shareholder yield
+        const state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.strictEqual(state.totalAsset.toNumber(), 1_005 * UNIT);
+        assert.strictEqual(state.totalShares.toNumber(), 1_000 * UNIT);
+        assert.strictEqual(state.flashLoanAmount.toNumber(), 0);
+    });
+
+    it("refuses a borrow without a later repay", async () => {
+        try {
+            await send(await borrowIx(100 * UNIT));
+            assert.fail("borrowed without repaying");
+        } catch (err: any) {
+            assert.include(err.toString(), "0x1792"); // FlashLoanNotRepaid
+        }
+    });
+
+    it("has its own pause flag", async () => {
+        await setFlashPause(true);
+        try {
+            await send(await borrowIx(100 * UNIT), await repayIx());
+            assert.fail("borrowed while flash loans are paused");
+        } catch (err: any) {
+            assert.include(err.toString(), "0x1790"); // FlashLoansPaused
+        } finally {
+            await setFlashPause(false);
+        }
+
+        // the vault-wide pause flag is untouched
+        const state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.isFalse(state.paused);
+    });
+});
@@ -0,0 +1,79 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_2022_PROGRAM_ID, getAccount } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    getTokenBalance,
+} from "./helpers";
+
+const SANCTIONS_HIT = 1;
+
+describe("holder freeze", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let user: UserFixture;
+
+    const holderAction = (method: "freezeHolder" | "thawHolder", signer?: anchor.web3.Keypair) =>
+        program.methods[method](SANCTIONS_HIT)
+            .accounts({
+                vaultState: vault.vaultState,
+                shareMint: vault.shareMint,
+                holderShareAccount: user.shareAccount,
+                vaultAuthority: vault.vaultAuthority,
+                admin: signer ? signer.publicKey : provider.wallet.publicKey,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            } as any)
+            .signers(signer ? [signer] : [])
+            .rpc();
+
+    const isFrozen = async () =>
+        (await getAccount(connection, user.shareAccount, undefined, TOKEN_2022_PROGRAM_ID))
+            .isFrozen;
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        user = await setupUser(provider, vault, 1_000 * UNIT);
+        await deposit(program, vault, user, 100 * UNIT);
+    });
+
+    it("only the admin can freeze a holder", async () => {
+        try {
+            await holderAction("freezeHolder", anchor.web3.Keypair.generate());
+            assert.fail("non-admin froze a holder");
+        } catch (err: any) {
+            assert.include(err.toString(), "ConstraintHasOne");
+        }
+    });
+
+    it("freezes a holder's shares so they can't be redeemed", async () => {
+        await holderAction("freezeHolder");
+        assert.isTrue(await isFrozen());
+
+        try {
+            await withdraw(program, vault, user, 10 * UNIT);
+            assert.fail("frozen shares were burned");
+        } catch (err: any) {
+            assert.include(err.toString(), "frozen");
+        }
+    });
+
+    it("thaws the holder again", async () => {
+        await holderAction("thawHolder");
+        assert.isFalse(await isFrozen());
+
+        await withdraw(program, vault, user, 10 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, user.shareAccount), 90 * UNIT);
+    });
+});
@@ -0,0 +1,309 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+    TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    createMint,
+    getOrCreateAssociatedTokenAccount,
+    mintTo,
+} from "@solana/spl-token";
+import { RezaVault } from "../target/types/reza_vault";
+
+export const DECIMALS = 6;
+export const UNIT = 10 ** DECIMALS;
+
+/** Share-token metadata written by `setupVault`. */
+export const SHARE_NAME = "Reza Vault Share";
+export const SHARE_SYMBOL = "rvSHARE";
+export const SHARE_URI = "https://example.com/reza-vault-share.json";
+
+export type VaultFixture = {
+    vaultId: anchor.BN;
+    assetMint: anchor.web3.PublicKey;
+    shareMint: anchor.web3.PublicKey;
+    vaultState: anchor.web3.PublicKey;
+    vaultAuthority: anchor.web3.PublicKey;
+    vaultAssetAccount: anchor.web3.PublicKey;
+};
+
+export type UserFixture = {
+    keypair: anchor.web3.Keypair;
+    assetAccount: anchor.web3.PublicKey;
+    shareAccount: anchor.web3.PublicKey;
+};
+
+export const sleep = (ms: number) => new Promise((r) => setTimeout(r, ms));
+
+/** Raw balance of an SPL Token or Token-2022 (share) account. */
+export const getTokenBalance = async (
+    connection: anchor.web3.Connection,
+    acc: anchor.web3.PublicKey
+) => Number((await connection.getTokenAccountBalance(acc)).value.amount);
+
+export const protocolConfigPda = (program: Program<RezaVault>) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("protocol_config")],
+        program.programId
+    )[0];
+
+export const vaultRegistryPda = (program: Program<RezaVault>, index: anchor.BN) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("vault_registry"), index.toArrayLike(Buffer, "le", 8)],
+        program.programId
+    )[0];
+
+/**
+ * Creates the protocol config on first use (owned by the provider wallet,
+ * no fee share) and allow-lists `assetMint` for new vaults. Returns the
+ * registry slot the next `initialize_vault` will fill.
+ */
+export const prepareProtocol = async (
+    program: Program<RezaVault>,
+    provider: anchor.AnchorProvider,
+    assetMint: anchor.web3.PublicKey
+) => {
+    const protocolConfig = protocolConfigPda(program);
+    if (!(await provider.connection.getAccountInfo(protocolConfig))) {
+        await program.methods
+            .initializeProtocol(0)
+            .accounts({
+                protocolConfig,
+                owner: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .rpc();
+    }
+    await program.methods
+        .addAllowedMint(assetMint)
+        .accounts({ protocolConfig, owner: provider.wallet.publicKey } as any)
+        .rpc();
+
+    const config = (await program.account.protocolConfig.fetch(protocolConfig)) as any;
+    return {
+        protocolConfig,
+        vaultRegistry: vaultRegistryPda(program, config.vaultCount),
+    };
+};
+
+export type VaultOptions = {
+    vaultId?: anchor.BN;
+    /** Soulbound shares (Token-2022 non-transferable extension). */
+    nonTransferable?: boolean;
+    /** Program written into the share mint's transfer-hook extension. */
+    transferHookProgram?: anchor.web3.PublicKey;
+    /** Existing Asset-A mint (e.g. `NATIVE_MINT`) instead of a fresh one. */
+    assetMint?: anchor.web3.PublicKey;
+};
+
+/**
+ * Creates a fresh Asset-A mint (unless one is given) and initialises a
+ * vault for it, deriving every PDA exactly as the program does.
+ */
+export const setupVault = async (
+    program: Program<RezaVault>,
+    provider: anchor.AnchorProvider,
+    {
+        vaultId = new anchor.BN(0),
+        nonTransferable = false,
+        transferHookProgram,
+        assetMint: existingMint,
+    }: VaultOptions = {}
+): Promise<VaultFixture> => {
+    const payer = (provider.wallet as any).payer;
+    const assetMint =
+        existingMint ??
+        (await createMint(
+            provider.connection,
+            payer,
+            provider.wallet.publicKey,
+            null,
+            DECIMALS
+        ));
+
+    const [vaultState, stateBump] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+            Buffer.from("vault_state"),
+            assetMint.toBuffer(),
+            vaultId.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+    );
+    const [vaultAuthority, authBump] =
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("vault_authority"), vaultState.toBuffer()],
+            program.programId
+        );
+    const [vaultAssetAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+            Buffer.from("vault_asset"),
+            assetMint.toBuffer(),
+            vaultState.toBuffer(),
+            vaultId.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+    );
+
+    const { protocolConfig, vaultRegistry } = await prepareProtocol(
+        program,
+        provider,
+        assetMint
+    );
+
+    const shareMintKP = anchor.web3.Keypair.generate();
+    await program.methods
+        .initializeVault(
+            stateBump,
+            authBump,
+            vaultId,
+            SHARE_NAME,
+            SHARE_SYMBOL,
+            SHARE_URI,
+            nonTransferable
+        )
+        .accounts({
+            vaultState,
+            vaultAuthority,
+            vaultAssetAccount,
+            admin: provider.wallet.publicKey,
+            assetMint,
+            shareMint: shareMintKP.publicKey,
+            protocolConfig,
+            vaultRegistry,
+            transferHookProgram: transferHookProgram ?? null,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        } as any)
+        .signers([shareMintKP])
+        .rpc();
+
+    return {
+        vaultId,
+        assetMint,
+        shareMint: shareMintKP.publicKey,
+        vaultState,
+        vaultAuthority,
+        vaultAssetAccount,
+    };
+};
+
+/**
+ * Funds a new user with SOL and `assetAmount` Asset-A units and creates
+ * their Asset-A and (Token-2022) share ATAs.
+ */
+export const setupUser = async (
+    provider: anchor.AnchorProvider,
+    vault: VaultFixture,
+    assetAmount: number
+): Promise<UserFixture> => {
+    const connection = provider.connection;
+    const payer = (provider.wallet as any).payer;
+    const keypair = anchor.web3.Keypair.generate();
+
+    const sig = await connection.requestAirdrop(
+        keypair.publicKey,
+        anchor.web3.LAMPORTS_PER_SOL * 2
+    );
+    await connection.confirmTransaction(sig, "confirmed");
+
+    const assetAccount = (
+        await getOrCreateAssociatedTokenAccount(
+            connection,
+            payer,
+            vault.assetMint,
+            keypair.publicKey
+        )
+    ).address;
+    if (assetAmount > 0) {
+        await mintTo(
+            connection,
+            payer,
+            vault.assetMint,
+            assetAccount,
+            provider.wallet.publicKey,
+            assetAmount
+        );
+    }
+
+    const shareAccount = (
+        await getOrCreateAssociatedTokenAccount(
+            connection,
+            payer,
+            vault.shareMint,
+            keypair.publicKey,
+            false,
+            undefined,
+            undefined,
+            TOKEN_2022_PROGRAM_ID
+        )
+    ).address;
+
+    return { keypair, assetAccount, shareAccount };
+};
+
+/**
+ * Plain `deposit_asset_a` call with the accounts of `user`; `extra` adds or
+ * overrides accounts (optional ones default to none), `remaining` carries the
+ * reward checkpoint pairs of a vault with reward pools.
+ */
+export const deposit = (
+    program: Program<RezaVault>,
+    vault: VaultFixture,
+    user: UserFixture,
+    amount: number,
+    extra: Record<string, anchor.web3.PublicKey | null> = {},
+    remaining: anchor.web3.AccountMeta[] = []
+) =>
+    program.methods
+        .depositAssetA(new anchor.BN(amount))
+        .accounts({
+            vaultState: vault.vaultState,
+            vaultAssetAccount: vault.vaultAssetAccount,
+            userAssetAccount: user.assetAccount,
+            receiver: user.keypair.publicKey,
+            receiverShareAccount: user.shareAccount,
+            shareMint: vault.shareMint,
+            vaultAuthority: vault.vaultAuthority,
+            protocolConfig: protocolConfigPda(program),
+            position: null,
+            user: user.keypair.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            ...extra,
+        } as any)
+        .remainingAccounts(remaining)
+        .signers([user.keypair])
+        .rpc();
+
+/** Plain `withdraw_asset_a` call with the accounts of `user`, see `deposit`. */
+export const withdraw = (
+    program: Program<RezaVault>,
+    vault: VaultFixture,
+    user: UserFixture,
+    shares: number,
+    extra: Record<string, anchor.web3.PublicKey | null> = {},
+    closeIfEmpty = false
+) =>
+    program.methods
+        .withdrawAssetA(new anchor.BN(shares), closeIfEmpty)
+        .accounts({
+            vaultState: vault.vaultState,
+            vaultAssetAccount: vault.vaultAssetAccount,
+            receiverAssetAccount: user.assetAccount,
+            userShareAccount: user.shareAccount,
+            shareMint: vault.shareMint,
+            vaultAuthority: vault.vaultAuthority,
+            protocolConfig: protocolConfigPda(program),
+            position: null,
+            queuedWithdrawal: null,
+            operator: null,
+            user: user.keypair.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            ...extra,
+        } as any)
+        .signers([user.keypair])
+        .rpc();
@@ -0,0 +1,128 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, approve } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    getTokenBalance,
+    protocolConfigPda,
+} from "./helpers";
+
+describe("deposit intents", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let user: UserFixture;
+    // the relayer is the provider wallet: it signs and pays, the user never does
+
+    const u64 = (value: number) => new anchor.BN(value).toArrayLike(Buffer, "le", 8);
+
+    /** Borsh layout of `DepositIntent`. */
+    const intentMessage = (amount: number, minShares: number, nonce: number, expiry: number) =>
+        Buffer.concat([vault.vaultState.toBuffer(), u64(amount), u64(minShares), u64(nonce), u64(expiry)]);
+
+    const intentNoncePda = () =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("intent_nonce"), vault.vaultState.toBuffer(), user.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const relay = async (
+        signed: { amount: number; minShares: number; nonce: number; expiry: number },
+        submittedAmount = signed.amount
+    ) => {
+        const verifyIx = anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+            privateKey: user.keypair.secretKey,
+            message: intentMessage(signed.amount, signed.minShares, signed.nonce, signed.expiry),
+        });
+        const depositIx = await program.methods
+            .depositWithIntent(
+                new anchor.BN(submittedAmount),
+                new anchor.BN(signed.minShares),
+                new anchor.BN(signed.nonce),
+                new anchor.BN(signed.expiry)
+            )
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                userAssetAccount: user.assetAccount,
+                shareMint: vault.shareMint,
+                receiver: user.keypair.publicKey,
+                receiverShareAccount: user.shareAccount,
+                vaultAuthority: vault.vaultAuthority,
+                position: null,
+                protocolConfig: protocolConfigPda(program),
+                intentNonce: intentNoncePda(),
+                instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+                user: provider.wallet.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .instruction();
+        return provider.sendAndConfirm(new anchor.web3.Transaction().add(verifyIx, depositIx));
+    };
+
+    const inAnHour = () => Math.floor(Date.now() / 1000) + 3_600;
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        user = await setupUser(provider, vault, 1_000 * UNIT);
+
+        // one-off pre-approval of the vault authority as delegate
+        await approve(
+            connection,
+            (provider.wallet as any).payer,
+            user.assetAccount,
+            vault.vaultAuthority,
+            user.keypair,
+            500 * UNIT
+        );
+    });
+
+    it("deposits for the user from a relayed, signed intent", async () => {
+        await relay({ amount: 100 * UNIT, minShares: 100 * UNIT, nonce: 0, expiry: inAnHour() });
+
+        assert.strictEqual(await getTokenBalance(connection, user.shareAccount), 100 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, user.assetAccount), 900 * UNIT);
+
+        const nonce = (await program.account.intentNonce.fetch(intentNoncePda())) as any;
+        assert.strictEqual(nonce.nextNonce.toNumber(), 1);
+    });
+
+    it("refuses to replay a used nonce", async () => {
+        try {
+            await relay({ amount: 100 * UNIT, minShares: 0, nonce: 0, expiry: inAnHour() });
+            assert.fail("replayed an intent");
+        } catch (err: any) {
+            assert.include(err.toString(), "0x1796"); // IntentNonceUsed
+        }
+    });
+
+    it("refuses an amount the user did not sign", async () => {
+        try {
+            await relay({ amount: 10 * UNIT, minShares: 0, nonce: 1, expiry: inAnHour() }, 200 * UNIT);
+            assert.fail("relayed a tampered intent");
+        } catch (err: any) {
+            assert.include(err.toString(), "0x1794"); // InvalidIntent
+        }
+    });
+
+    it("refuses an expired intent", async () => {
+        try {
+            await relay({ amount: 10 * UNIT, minShares: 0, nonce: 1, expiry: 1 });
+            assert.fail("relayed an expired intent");
+        } catch (err: any) {
+            assert.include(err.toString(), "0x1795"); // IntentExpired
+        }
+        assert.strictEqual(await getTokenBalance(connection, user.shareAccount), 100 * UNIT);
+    });
+});
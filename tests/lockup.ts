@@ -0,0 +1,117 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    getTokenBalance,
+} from "./helpers";
+
+describe("lockup", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let user: UserFixture;
+    let stranger: UserFixture;
+
+    const positionPda = (owner: anchor.web3.PublicKey) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("position"), vault.vaultState.toBuffer(), owner.toBuffer()],
+            program.programId
+        )[0];
+
+    const setLockup = (period: number, penaltyBps: number) =>
+        program.methods
+            .setLockup(new anchor.BN(period), penaltyBps)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+    before(async () => {
+        // soulbound shares: a lockup needs transfers blocked or hooked
+        vault = await setupVault(program, provider, { nonTransferable: true });
+        user = await setupUser(provider, vault, 1_000 * UNIT);
+        stranger = await setupUser(provider, vault, 10 * UNIT);
+        await setLockup(3_600, 0);
+    });
+
+    it("refuses a lockup on freely transferable shares", async () => {
+        const plain = await setupVault(program, provider);
+        try {
+            await program.methods
+                .setLockup(new anchor.BN(3_600), 0)
+                .accounts({ vaultState: plain.vaultState, admin: provider.wallet.publicKey } as any)
+                .rpc();
+            assert.fail("lockup set without the transfer hook");
+        } catch (err: any) {
+            assert.include(err.toString(), "LockupNeedsTransferHook");
+        }
+    });
+
+    it("records locked shares and an unlock time on deposit", async () => {
+        await deposit(program, vault, user, 100 * UNIT, {
+            position: positionPda(user.keypair.publicKey),
+        });
+
+        const position = (await program.account.position.fetch(
+            positionPda(user.keypair.publicKey)
+        )) as any;
+        assert.strictEqual(position.lockedShares.toNumber(), 100 * UNIT);
+        assert.ok(position.unlockTs.toNumber() > Date.now() / 1000);
+    });
+
+    it("does not let a third-party deposit push the unlock time out", async () => {
+        const position = positionPda(user.keypair.publicKey);
+        const before = (await program.account.position.fetch(position)) as any;
+
+        await deposit(program, vault, stranger, 1, {
+            receiver: user.keypair.publicKey,
+            receiverShareAccount: user.shareAccount,
+            position,
+        });
+
+        const after = (await program.account.position.fetch(position)) as any;
+        assert.strictEqual(after.unlockTs.toNumber(), before.unlockTs.toNumber());
+        assert.strictEqual(after.lockedShares.toNumber(), 100 * UNIT + 1);
+    });
+
+    it("refuses to burn locked shares without a penalty configured", async () => {
+        try {
+            await withdraw(program, vault, user, 10 * UNIT, {
+                position: positionPda(user.keypair.publicKey),
+            });
+            assert.fail("locked shares were burned");
+        } catch (err: any) {
+            assert.include(err.toString(), "SharesLocked");
+        }
+    });
+
+    it("charges the early-exit penalty to the leaver, not the vault", async () => {
+        await setLockup(3_600, 1_000); // 10 %
+
+        await withdraw(program, vault, user, 10 * UNIT, {
+            position: positionPda(user.keypair.publicKey),
+        });
+
+        // 10 shares worth 10 tokens, minus 1 token penalty
+        assert.strictEqual(await getTokenBalance(connection, user.assetAccount), 909 * UNIT);
+
+        const state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        // plus the stranger's single unit
+        assert.strictEqual(state.totalShares.toNumber(), 90 * UNIT + 1);
+        assert.strictEqual(state.totalAsset.toNumber(), 91 * UNIT + 1);
+
+        const position = (await program.account.position.fetch(
+            positionPda(user.keypair.publicKey)
+        )) as any;
+        assert.strictEqual(position.lockedShares.toNumber(), 90 * UNIT + 1);
+    });
+});
@@ -0,0 +1,145 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+    NATIVE_MINT,
+    TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    getTokenBalance,
+    protocolConfigPda,
+} from "./helpers";
+
+const SOL = anchor.web3.LAMPORTS_PER_SOL;
+
+describe("native SOL vault", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let user: UserFixture;
+    const coldWallet = anchor.web3.Keypair.generate().publicKey;
+
+    const depositSol = (vault: VaultFixture, lamports: number) =>
+        program.methods
+            .depositSol(new anchor.BN(lamports))
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                userAssetAccount: null,
+                receiver: user.keypair.publicKey,
+                receiverShareAccount: getAssociatedTokenAddressSync(
+                    vault.shareMint,
+                    user.keypair.publicKey,
+                    false,
+                    TOKEN_2022_PROGRAM_ID
+                ),
+                shareMint: vault.shareMint,
+                vaultAuthority: vault.vaultAuthority,
+                protocolConfig: protocolConfigPda(program),
+                position: null,
+                user: user.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([user.keypair])
+            .rpc();
+
+    const unwrapPda = () =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("sol_unwrap"),
+                vault.vaultState.toBuffer(),
+                user.keypair.publicKey.toBuffer(),
+            ],
+            program.programId
+        )[0];
+
+    const withdrawSol = (lamports: number, queuedWithdrawal: anchor.web3.PublicKey | null = null) =>
+        program.methods
+            .withdrawSol(new anchor.BN(lamports), false)
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                receiverAssetAccount: null,
+                userShareAccount: user.shareAccount,
+                shareMint: vault.shareMint,
+                vaultAuthority: vault.vaultAuthority,
+                position: null,
+                queuedWithdrawal,
+                operator: null,
+                nativeMint: NATIVE_MINT,
+                solUnwrapAccount: unwrapPda(),
+                solReceiver: coldWallet,
+                protocolConfig: protocolConfigPda(program),
+                user: user.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([user.keypair])
+            .rpc();
+
+    before(async () => {
+        vault = await setupVault(program, provider, { assetMint: NATIVE_MINT });
+        user = await setupUser(provider, vault, 0);
+    });
+
+    it("wraps deposited lamports into the vault and mints shares", async () => {
+        const before = await connection.getBalance(user.keypair.publicKey);
+        await depositSol(vault, SOL / 2);
+
+        assert.strictEqual(await getTokenBalance(connection, vault.vaultAssetAccount), SOL / 2);
+        assert.strictEqual(await getTokenBalance(connection, user.shareAccount), SOL / 2);
+        assert.isAtMost(await connection.getBalance(user.keypair.publicKey), before - SOL / 2);
+    });
+
+    it("unwraps withdrawn shares into lamports for the receiver", async () => {
+        await withdrawSol(SOL / 5);
+
+        assert.strictEqual(await connection.getBalance(coldWallet), SOL / 5);
+        assert.strictEqual(await getTokenBalance(connection, user.shareAccount), (3 * SOL) / 10);
+        assert.strictEqual(await getTokenBalance(connection, vault.vaultAssetAccount), (3 * SOL) / 10);
+        assert.isNull(await connection.getAccountInfo(unwrapPda()));
+    });
+
+    it("closes the scratch account when the gate queues the withdrawal", async () => {
+        const [queuedWithdrawal] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("queued_withdrawal"), vault.vaultState.toBuffer(), user.keypair.publicKey.toBuffer()],
+            program.programId
+        );
+        await program.methods
+            .setRedemptionGate(1_000, new anchor.BN(3_600), 0) // 10 % per hour
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+        // both exceed the 0.03 SOL window cap and are queued
+        await withdrawSol(SOL / 10, queuedWithdrawal);
+        assert.isNull(await connection.getAccountInfo(unwrapPda()));
+        await withdrawSol(SOL / 10, queuedWithdrawal);
+
+        const queued = (await program.account.queuedWithdrawal.fetch(queuedWithdrawal)) as any;
+        assert.strictEqual(queued.shares.toNumber(), SOL / 5);
+        assert.strictEqual(await connection.getBalance(coldWallet), SOL / 5);
+    });
+
+    it("refuses lamport deposits into a non-native vault", async () => {
+        const splVault = await setupVault(program, provider);
+        try {
+            await depositSol(splVault, SOL / 10);
+            assert.fail("wrapped SOL into an SPL vault");
+        } catch (err: any) {
+            assert.include(err.toString(), "NotNativeVault");
+        }
+    });
+});
@@ -0,0 +1,153 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_2022_PROGRAM_ID, approve, getAccount } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    getTokenBalance,
+} from "./helpers";
+
+describe("delegated redemption", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let holder: UserFixture;
+    let rebalancer: UserFixture;
+    let keeper: UserFixture;
+
+    const operatorPda = (owner: UserFixture) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("operator"), vault.vaultState.toBuffer(), owner.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const approveOperator = (operator: UserFixture, allowance: number) =>
+        program.methods
+            .approveOperator(new anchor.BN(allowance))
+            .accounts({
+                vaultState: vault.vaultState,
+                userShareAccount: holder.shareAccount,
+                operator: operator.keypair.publicKey,
+                owner: holder.keypair.publicKey,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            } as any)
+            .signers([holder.keypair])
+            .rpc();
+
+    // `signer` signs, the holder's shares are burned, the holder is paid
+    const redeemFor = (
+        shares: number,
+        extra: Record<string, anchor.web3.PublicKey | null> = {},
+        signer: UserFixture = rebalancer
+    ) =>
+        withdraw(program, vault, signer, shares, {
+            userShareAccount: holder.shareAccount,
+            receiverAssetAccount: holder.assetAccount,
+            ...extra,
+        });
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        holder = await setupUser(provider, vault, 1_000 * UNIT);
+        rebalancer = await setupUser(provider, vault, 0);
+        keeper = await setupUser(provider, vault, 0);
+        await deposit(program, vault, holder, 100 * UNIT);
+    });
+
+    it("redeems through an SPL delegate and spends the delegation", async () => {
+        await approve(
+            connection,
+            (provider.wallet as any).payer,
+            holder.shareAccount,
+            rebalancer.keypair.publicKey,
+            holder.keypair,
+            20 * UNIT,
+            [],
+            undefined,
+            TOKEN_2022_PROGRAM_ID
+        );
+
+        await redeemFor(15 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, holder.shareAccount), 85 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, holder.assetAccount), 915 * UNIT);
+
+        const account = await getAccount(connection, holder.shareAccount, undefined, TOKEN_2022_PROGRAM_ID);
+        assert.strictEqual(Number(account.delegatedAmount), 5 * UNIT);
+
+        try {
+            await redeemFor(10 * UNIT);
+            assert.fail("redeemed past the delegation");
+        } catch (err: any) {
+            assert.include(err.toString(), "0x1"); // InsufficientFunds
+        }
+    });
+
+    it("redeems as a vault-level operator within its allowance", async () => {
+        await approveOperator(rebalancer, 30 * UNIT);
+
+        const operator = operatorPda(holder);
+        await redeemFor(25 * UNIT, { operator });
+        assert.strictEqual(await getTokenBalance(connection, holder.shareAccount), 60 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, holder.assetAccount), 940 * UNIT);
+
+        const allowance = (await program.account.operator.fetch(operator)) as any;
+        assert.strictEqual(allowance.allowance.toNumber(), 5 * UNIT);
+
+        try {
+            await redeemFor(10 * UNIT, { operator });
+            assert.fail("redeemed past the operator allowance");
+        } catch (err: any) {
+            assert.include(err.toString(), "AllowanceExceeded");
+        }
+    });
+
+    it("replaces the operator when the holder approves another", async () => {
+        const operator = operatorPda(holder);
+        await approveOperator(keeper, 10 * UNIT);
+
+        try {
+            await redeemFor(1 * UNIT, { operator });
+            assert.fail("the replaced operator redeemed");
+        } catch (err: any) {
+            assert.include(err.toString(), "NotOperator");
+        }
+
+        await redeemFor(10 * UNIT, { operator }, keeper);
+        assert.strictEqual(await getTokenBalance(connection, holder.shareAccount), 50 * UNIT);
+        const allowance = (await program.account.operator.fetch(operator)) as any;
+        assert.strictEqual(allowance.allowance.toNumber(), 0);
+    });
+
+    it("stops the operator once revoked", async () => {
+        const operator = operatorPda(holder);
+        await program.methods
+            .revokeOperator()
+            .accounts({
+                vaultState: vault.vaultState,
+                userShareAccount: holder.shareAccount,
+                operatorAllowance: operator,
+                owner: holder.keypair.publicKey,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            } as any)
+            .signers([holder.keypair])
+            .rpc();
+
+        assert.isNull(await connection.getAccountInfo(operator));
+        try {
+            await redeemFor(1 * UNIT);
+            assert.fail("redeemed without any approval");
+        } catch (err: any) {
+            assert.include(err.toString(), "0x4"); // OwnerMismatch
+        }
+    });
+});
@@ -0,0 +1,116 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import { MockOracle } from "../target/types/mock_oracle";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+} from "./helpers";
+
+describe("oracle NAV in USD", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+    const oracle = anchor.workspace.MockOracle as Program<MockOracle>;
+
+    const priceUpdate = anchor.web3.Keypair.generate();
+    let vault: VaultFixture;
+    let alice: UserFixture;
+
+    const now = () => Math.floor(Date.now() / 1000);
+
+    // Asset A at `usd` dollars, published `age` seconds ago
+    const setPrice = (usd: number, confBps = 5, age = 0) => {
+        const price = usd * 10 ** 8;
+        return oracle.methods
+            .setPrice(new anchor.BN(price), new anchor.BN((price * confBps) / 10_000), -8, new anchor.BN(now() - age))
+            .accounts({ priceUpdate: priceUpdate.publicKey, writeAuthority: provider.wallet.publicKey } as any)
+            .rpc();
+    };
+
+    const navUsd = () =>
+        program.methods
+            .getNavUsd()
+            .accounts({ vaultState: vault.vaultState, oracle: priceUpdate.publicKey } as any)
+            .view();
+
+    const depositWithOracle = (amount: number) =>
+        deposit(program, vault, alice, amount, { assetOracle: priceUpdate.publicKey });
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        alice = await setupUser(provider, vault, 1_000 * UNIT);
+
+        await oracle.methods
+            .initializePrice([...Buffer.alloc(32, 1)])
+            .accounts({
+                priceUpdate: priceUpdate.publicKey,
+                authority: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([priceUpdate])
+            .rpc();
+        await setPrice(2);
+
+        await program.methods
+            .setOracle(priceUpdate.publicKey, new anchor.BN(600), 100) // 10 min, 1 %
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+    });
+
+    it("reports TVL and share price in USD", async () => {
+        await deposit(program, vault, alice, 100 * UNIT);
+
+        const nav = (await navUsd()) as any;
+        assert.strictEqual(nav.tvlUsd.toNumber(), 200 * 10 ** 6);
+        assert.strictEqual(nav.sharePriceUsd.toNumber(), 2 * 10 ** 6);
+        assert.strictEqual(nav.oraclePrice.toNumber(), 2 * 10 ** 8);
+        assert.strictEqual(nav.oracleExpo, -8);
+    });
+
+    it("caps deposits by USD value", async () => {
+        await program.methods
+            .setDepositCapUsd(new anchor.BN(300 * 10 ** 6))
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+        try {
+            await deposit(program, vault, alice, 50 * UNIT);
+            assert.fail("deposited past the cap without the oracle");
+        } catch (err: any) {
+            assert.include(err.toString(), "OracleRequired");
+        }
+        try {
+            await depositWithOracle(100 * UNIT); // $400 > $300
+            assert.fail("deposited past the USD cap");
+        } catch (err: any) {
+            assert.include(err.toString(), "DepositCapExceeded");
+        }
+        await depositWithOracle(50 * UNIT); // exactly $300
+    });
+
+    it("rejects stale prices", async () => {
+        await setPrice(2, 5, 3_600);
+        try {
+            await navUsd();
+            assert.fail("priced with an hour-old update");
+        } catch (err: any) {
+            assert.include(err.toString(), "OracleStale");
+        }
+    });
+
+    it("rejects prices with a wide confidence interval", async () => {
+        await setPrice(2, 500); // ±5 %
+        try {
+            await navUsd();
+            assert.fail("priced with a 5 % confidence interval");
+        } catch (err: any) {
+            assert.include(err.toString(), "OracleConfidenceTooWide");
+        }
+    });
+});
@@ -0,0 +1,89 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+} from "./helpers";
+
+describe("share price history", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let alice: UserFixture;
+
+    const priceHistoryPda = () =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("price_history"), vault.vaultState.toBuffer()],
+            program.programId
+        )[0];
+
+    const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        alice = await setupUser(provider, vault, 1_000 * UNIT);
+
+        await program.methods
+            .createPriceHistory(new anchor.BN(1))
+            .accounts({
+                vaultState: vault.vaultState,
+                priceHistory: priceHistoryPda(),
+                admin: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .rpc();
+    });
+
+    it("records snapshots on deposits and syncs, at most once per interval", async () => {
+        await sleep(1_500);
+        await deposit(program, vault, alice, 100 * UNIT, { priceHistory: priceHistoryPda() });
+        await sleep(1_500);
+        await program.methods
+            .syncPriceHistory()
+            .accounts({ vaultState: vault.vaultState, priceHistory: priceHistoryPda() } as any)
+            .rpc();
+
+        const history = (await program.account.priceHistory.fetch(priceHistoryPda())) as any;
+        assert.strictEqual(history.len, 3);
+        const latest = history.snapshots[history.len - 1];
+        assert.strictEqual(latest.totalAsset.toNumber(), 100 * UNIT);
+        assert.strictEqual(latest.totalShares.toNumber(), 100 * UNIT);
+    });
+
+    it("returns the time-weighted price over a covered window", async () => {
+        const twap = await program.methods
+            .getTwap(new anchor.BN(2))
+            .accounts({ priceHistory: priceHistoryPda() } as any)
+            .view();
+        assert.strictEqual(twap.toNumber(), 1_000_000_000); // 1.0
+    });
+
+    it("requires the history on every state change once it exists", async () => {
+        try {
+            await deposit(program, vault, alice, 1 * UNIT);
+            assert.fail("deposited without recording the history");
+        } catch (err: any) {
+            assert.include(err.toString(), "PriceHistoryRequired");
+        }
+    });
+
+    it("refuses a window older than the history", async () => {
+        try {
+            await program.methods
+                .getTwap(new anchor.BN(3_600))
+                .accounts({ priceHistory: priceHistoryPda() } as any)
+                .view();
+            assert.fail("priced a window the history does not cover");
+        } catch (err: any) {
+            assert.include(err.toString(), "InsufficientPriceHistory");
+        }
+    });
+});
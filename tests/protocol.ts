@@ -0,0 +1,271 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, createMint } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    DECIMALS,
+    UNIT,
+    SHARE_NAME,
+    SHARE_SYMBOL,
+    SHARE_URI,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    getTokenBalance,
+    protocolConfigPda,
+    vaultRegistryPda,
+} from "./helpers";
+
+describe("protocol config", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let user: UserFixture;
+    let protocolConfig: anchor.web3.PublicKey;
+
+    const fetchConfig = async () =>
+        (await program.account.protocolConfig.fetch(protocolConfig)) as any;
+
+    const positionPda = () =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("position"), vault.vaultState.toBuffer(), user.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const setFeeShare = (bps: number) =>
+        program.methods
+            .setProtocolFeeShare(bps)
+            .accounts({ protocolConfig, owner: provider.wallet.publicKey } as any)
+            .rpc();
+
+    before(async () => {
+        // soulbound, so the early-exit test below may set a lockup
+        vault = await setupVault(program, provider, { nonTransferable: true });
+        user = await setupUser(provider, vault, 1_000 * UNIT);
+        protocolConfig = protocolConfigPda(program);
+    });
+
+    it("registers every new vault in the registry", async () => {
+        const before = (await fetchConfig()).vaultCount.toNumber();
+        const second = await setupVault(program, provider);
+
+        const config = await fetchConfig();
+        assert.strictEqual(config.vaultCount.toNumber(), before + 1);
+
+        const entry = (await program.account.vaultRegistryEntry.fetch(
+            vaultRegistryPda(program, new anchor.BN(before))
+        )) as any;
+        assert.ok(entry.vault.equals(second.vaultState));
+        assert.ok(entry.assetMint.equals(second.assetMint));
+        assert.strictEqual(entry.index.toNumber(), before);
+    });
+
+    it("rejects vaults for mints that are not allow-listed", async () => {
+        const payer = (provider.wallet as any).payer;
+        const assetMint = await createMint(
+            connection,
+            payer,
+            provider.wallet.publicKey,
+            null,
+            DECIMALS
+        );
+        const vaultId = new anchor.BN(0);
+        const [vaultState, stateBump] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("vault_state"), assetMint.toBuffer(), vaultId.toArrayLike(Buffer, "le", 8)],
+            program.programId
+        );
+        const [vaultAuthority, authBump] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("vault_authority"), vaultState.toBuffer()],
+            program.programId
+        );
+        const [vaultAssetAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("vault_asset"),
+                assetMint.toBuffer(),
+                vaultState.toBuffer(),
+                vaultId.toArrayLike(Buffer, "le", 8),
+            ],
+            program.programId
+        );
+        const shareMint = anchor.web3.Keypair.generate();
+        const config = await fetchConfig();
+
+        try {
+            await program.methods
+                .initializeVault(
+                    stateBump,
+                    authBump,
+                    vaultId,
+                    SHARE_NAME,
+                    SHARE_SYMBOL,
+                    SHARE_URI,
+                    false
+                )
+                .accounts({
+                    vaultState,
+                    vaultAuthority,
+                    vaultAssetAccount,
+                    admin: provider.wallet.publicKey,
+                    assetMint,
+                    shareMint: shareMint.publicKey,
+                    protocolConfig,
+                    vaultRegistry: vaultRegistryPda(program, config.vaultCount),
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                } as any)
+                .signers([shareMint])
+                .rpc();
+            assert.fail("vault created for an unlisted mint");
+        } catch (err: any) {
+            assert.include(err.toString(), "AssetMintNotAllowed");
+        }
+    });
+
+    it("splits early-exit penalties with the protocol", async () => {
+        const position = positionPda();
+        await program.methods
+            .setLockup(new anchor.BN(3_600), 1_000) // 10 %
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+        await setFeeShare(5_000); // half of every fee
+
+        await deposit(program, vault, user, 100 * UNIT, { position });
+        await withdraw(program, vault, user, 10 * UNIT, { position });
+
+        // 1 token penalty: 0.5 stays with holders, 0.5 is owed to the protocol
+        let state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.strictEqual(state.protocolFeesAccrued.toNumber(), UNIT / 2);
+        assert.strictEqual(state.totalAsset.toNumber(), 90.5 * UNIT);
+
+        const treasury = await setupUser(provider, vault, 0);
+        await program.methods
+            .collectProtocolFees()
+            .accounts({
+                protocolConfig,
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                destination: treasury.assetAccount,
+                vaultAuthority: vault.vaultAuthority,
+                owner: provider.wallet.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            } as any)
+            .rpc();
+
+        assert.strictEqual(await getTokenBalance(connection, treasury.assetAccount), UNIT / 2);
+        state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.strictEqual(state.protocolFeesAccrued.toNumber(), 0);
+        assert.strictEqual(
+            await getTokenBalance(connection, vault.vaultAssetAccount),
+            state.totalAsset.toNumber()
+        );
+    });
+
+    it("splits flash-loan fees with the protocol", async () => {
+        await program.methods
+            .setFlashLoanFee(1_000) // 10 %
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+        const borrower = await setupUser(provider, vault, 1 * UNIT);
+        const before = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+
+        const accounts = {
+            vaultState: vault.vaultState,
+            vaultAssetAccount: vault.vaultAssetAccount,
+            borrowerAssetAccount: borrower.assetAccount,
+            protocolConfig,
+            borrower: borrower.keypair.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        };
+        const borrowIx = await program.methods
+            .flashBorrow(new anchor.BN(10 * UNIT))
+            .accounts({ ...accounts, instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY } as any)
+            .instruction();
+        const repayIx = await program.methods.flashRepay().accounts(accounts as any).instruction();
+        await provider.sendAndConfirm(new anchor.web3.Transaction().add(borrowIx, repayIx), [
+            borrower.keypair,
+        ]);
+
+        // 1 token fee: 0.5 to the holders, 0.5 owed to the protocol
+        const state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.strictEqual(state.totalAsset.toNumber() - before.totalAsset.toNumber(), UNIT / 2);
+        assert.strictEqual(
+            state.protocolFeesAccrued.toNumber() - before.protocolFeesAccrued.toNumber(),
+            UNIT / 2
+        );
+    });
+
+    it("lets the guardian freeze every vault at once", async () => {
+        const guardian = anchor.web3.Keypair.generate();
+        await program.methods
+            .setGuardian(guardian.publicKey)
+            .accounts({ protocolConfig, owner: provider.wallet.publicKey } as any)
+            .rpc();
+
+        const setGlobalPause = (pause: boolean) =>
+            program.methods
+                .setGlobalPause(pause)
+                .accounts({ protocolConfig, guardian: guardian.publicKey } as any)
+                .signers([guardian])
+                .rpc();
+
+        await setGlobalPause(true);
+        try {
+            try {
+                await deposit(program, vault, user, 1 * UNIT, { position: positionPda() });
+                assert.fail("deposit went through a global pause");
+            } catch (err: any) {
+                assert.include(err.toString(), "ProtocolPaused");
+            }
+            try {
+                await withdraw(program, vault, user, 1 * UNIT, { position: positionPda() });
+                assert.fail("withdraw went through a global pause");
+            } catch (err: any) {
+                assert.include(err.toString(), "ProtocolPaused");
+            }
+            // epoch requests are refused before the vault's mode is even looked at
+            try {
+                await program.methods
+                    .requestDeposit(new anchor.BN(1 * UNIT))
+                    .accounts({
+                        vaultState: vault.vaultState,
+                        vaultAssetAccount: vault.vaultAssetAccount,
+                        userAssetAccount: user.assetAccount,
+                        epochRequest: anchor.web3.PublicKey.findProgramAddressSync(
+                            [
+                                Buffer.from("epoch_request"),
+                                vault.vaultState.toBuffer(),
+                                user.keypair.publicKey.toBuffer(),
+                            ],
+                            program.programId
+                        )[0],
+                        protocolConfig,
+                        user: user.keypair.publicKey,
+                        tokenProgram: TOKEN_PROGRAM_ID,
+                        systemProgram: anchor.web3.SystemProgram.programId,
+                    } as any)
+                    .signers([user.keypair])
+                    .rpc();
+                assert.fail("epoch request went through a global pause");
+            } catch (err: any) {
+                assert.include(err.toString(), "ProtocolPaused");
+            }
+        } finally {
+            await setGlobalPause(false);
+        }
+        await deposit(program, vault, user, 1 * UNIT, { position: positionPda() });
+    });
+
+    after(async () => {
+        // the config is shared by every test file
+        await setFeeShare(0);
+    });
+});
@@ -0,0 +1,116 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_2022_PROGRAM_ID, getAssociatedTokenAddressSync } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    getTokenBalance,
+} from "./helpers";
+
+describe("receiver and owner", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let payroll: UserFixture;
+    let employee: UserFixture;
+    let coldWallet: UserFixture;
+
+    const eventOf = async (txSig: string, name: string) => {
+        await connection.confirmTransaction(txSig, "confirmed");
+        const tx = await connection.getTransaction(txSig, {
+            commitment: "confirmed",
+            maxSupportedTransactionVersion: 0,
+        });
+        const parser = new anchor.EventParser(program.programId, program.coder);
+        for (const event of parser.parseLogs(tx!.meta!.logMessages!)) {
+            if (event.name === name) return event.data as any;
+        }
+        assert.fail(`${name} not emitted`);
+    };
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        payroll = await setupUser(provider, vault, 1_000 * UNIT);
+        employee = await setupUser(provider, vault, 0);
+        coldWallet = await setupUser(provider, vault, 0);
+    });
+
+    it("deposits on behalf of another receiver", async () => {
+        const txSig = await deposit(program, vault, payroll, 100 * UNIT, {
+            receiver: employee.keypair.publicKey,
+            receiverShareAccount: employee.shareAccount,
+        });
+
+        assert.strictEqual(await getTokenBalance(connection, employee.shareAccount), 100 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, payroll.shareAccount), 0);
+        assert.strictEqual(await getTokenBalance(connection, payroll.assetAccount), 900 * UNIT);
+
+        const event = await eventOf(txSig, "depositEvent");
+        assert.isTrue(event.sender.equals(payroll.keypair.publicKey));
+        assert.isTrue(event.owner.equals(payroll.keypair.publicKey));
+        assert.isTrue(event.receiver.equals(employee.keypair.publicKey));
+    });
+
+    it("withdraws the owner's shares to a different receiver", async () => {
+        const txSig = await withdraw(program, vault, employee, 40 * UNIT, {
+            receiverAssetAccount: coldWallet.assetAccount,
+        });
+
+        assert.strictEqual(await getTokenBalance(connection, employee.shareAccount), 60 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, employee.assetAccount), 0);
+        assert.strictEqual(await getTokenBalance(connection, coldWallet.assetAccount), 40 * UNIT);
+
+        const event = await eventOf(txSig, "withdrawEvent");
+        assert.isTrue(event.sender.equals(employee.keypair.publicKey));
+        assert.isTrue(event.owner.equals(employee.keypair.publicKey));
+        assert.isTrue(event.receiver.equals(coldWallet.keypair.publicKey));
+    });
+
+    it("rejects a share account that is not the receiver's ATA", async () => {
+        try {
+            await deposit(program, vault, payroll, 10 * UNIT, {
+                receiver: employee.keypair.publicKey,
+                receiverShareAccount: coldWallet.shareAccount,
+            });
+            assert.fail("minted into another wallet's share account");
+        } catch (err: any) {
+            assert.include(err.toString(), "ConstraintTokenOwner");
+        }
+        assert.strictEqual(await getTokenBalance(connection, employee.shareAccount), 60 * UNIT);
+    });
+
+    it("creates a new receiver's share ATA and closes it once emptied", async () => {
+        const newcomer = {
+            keypair: anchor.web3.Keypair.generate(),
+            assetAccount: coldWallet.assetAccount,
+            shareAccount: anchor.web3.PublicKey.default,
+        };
+        newcomer.shareAccount = getAssociatedTokenAddressSync(
+            vault.shareMint,
+            newcomer.keypair.publicKey,
+            false,
+            TOKEN_2022_PROGRAM_ID
+        );
+        assert.isNull(await connection.getAccountInfo(newcomer.shareAccount));
+
+        await deposit(program, vault, payroll, 10 * UNIT, {
+            receiver: newcomer.keypair.publicKey,
+            receiverShareAccount: newcomer.shareAccount,
+        });
+        assert.strictEqual(await getTokenBalance(connection, newcomer.shareAccount), 10 * UNIT);
+
+        await withdraw(program, vault, newcomer, 10 * UNIT, {}, true);
+        assert.isNull(await connection.getAccountInfo(newcomer.shareAccount));
+        assert.isAbove(await connection.getBalance(newcomer.keypair.publicKey), 0);
+    });
+});
@@ -0,0 +1,147 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, approve } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    getTokenBalance,
+    protocolConfigPda,
+    sleep,
+} from "./helpers";
+
+describe("recurring deposits", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let saver: UserFixture;
+    let keeper: UserFixture;
+
+    const recurringPda = () =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("recurring"), vault.vaultState.toBuffer(), saver.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const crank = () =>
+        program.methods
+            .executeRecurringDeposit()
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                userAssetAccount: saver.assetAccount,
+                shareMint: vault.shareMint,
+                receiver: saver.keypair.publicKey,
+                receiverShareAccount: saver.shareAccount,
+                vaultAuthority: vault.vaultAuthority,
+                position: null,
+                protocolConfig: protocolConfigPda(program),
+                recurringDeposit: recurringPda(),
+                keeperAssetAccount: keeper.assetAccount,
+                user: keeper.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([keeper.keypair])
+            .rpc();
+
+    const schedule = (amount: number, interval: number, endTs: number) =>
+        program.methods
+            .createRecurringDeposit(new anchor.BN(amount), new anchor.BN(interval), new anchor.BN(endTs))
+            .accounts({
+                vaultState: vault.vaultState,
+                userAssetAccount: saver.assetAccount,
+                recurringDeposit: recurringPda(),
+                owner: saver.keypair.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([saver.keypair])
+            .rpc();
+
+    const crankFails = async () => {
+        try {
+            await crank();
+            assert.fail("ran a deposit that was not due");
+        } catch (err: any) {
+            assert.include(err.toString(), "RecurringDepositNotDue");
+        }
+    };
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        saver = await setupUser(provider, vault, 1_000 * UNIT);
+        keeper = await setupUser(provider, vault, 0);
+
+        await program.methods
+            .setCompoundConfig(anchor.web3.PublicKey.default, 100, 0) // 1 % keeper bounty
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+        await approve(
+            connection,
+            (provider.wallet as any).payer,
+            saver.assetAccount,
+            vault.vaultAuthority,
+            saver.keypair,
+            500 * UNIT
+        );
+        await schedule(100 * UNIT, 3_600, Math.floor(Date.now() / 1000) + 86_400);
+    });
+
+    it("lets any keeper run a due deposit for a bounty", async () => {
+        await crank();
+
+        assert.strictEqual(await getTokenBalance(connection, saver.assetAccount), 900 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, saver.shareAccount), 99 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, keeper.assetAccount), 1 * UNIT);
+    });
+
+    it("refuses to run again before the next interval", async () => {
+        await crankFails();
+        assert.strictEqual(await getTokenBalance(connection, saver.shareAccount), 99 * UNIT);
+    });
+
+    it("lets the owner cancel the schedule", async () => {
+        await program.methods
+            .cancelRecurringDeposit()
+            .accounts({
+                recurringDeposit: recurringPda(),
+                owner: saver.keypair.publicKey,
+            } as any)
+            .signers([saver.keypair])
+            .rpc();
+        assert.isNull(await connection.getAccountInfo(recurringPda()));
+    });
+
+    it("skips missed slots and closes the schedule after its last deposit", async () => {
+        const now = (await connection.getBlockTime(await connection.getSlot()))!;
+        await schedule(100 * UNIT, 4, now + 13);
+        await crank(); // due at creation, next one at +4
+        const rent = await connection.getBalance(recurringPda());
+
+        // two slots pass without a keeper: one deposit, the next at +12
+        await sleep(9_000);
+        await crank();
+        await crankFails();
+        assert.strictEqual(await getTokenBalance(connection, saver.assetAccount), 700 * UNIT);
+
+        // the deposit due at +12 is the last one before the end at +13
+        await sleep(3_500);
+        const lamportsBefore = await connection.getBalance(saver.keypair.publicKey);
+        await crank();
+        assert.isNull(await connection.getAccountInfo(recurringPda()));
+        assert.strictEqual(
+            await connection.getBalance(saver.keypair.publicKey),
+            lamportsBefore + rent
+        );
+        assert.strictEqual(await getTokenBalance(connection, saver.assetAccount), 600 * UNIT);
+    });
+});
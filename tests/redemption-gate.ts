@@ -0,0 +1,153 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    getTokenBalance,
+    sleep,
+} from "./helpers";
+
+describe("redemption gate", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let user: UserFixture;
+    let queuedWithdrawal: anchor.web3.PublicKey;
+
+    const fetchState = async () =>
+        (await program.account.vaultState.fetch(vault.vaultState)) as any;
+
+    const processQueue = () =>
+        program.methods
+            .processQueuedWithdrawal()
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                ownerAssetAccount: user.assetAccount,
+                queuedWithdrawal,
+                owner: user.keypair.publicKey,
+                vaultAuthority: vault.vaultAuthority,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            } as any)
+            .rpc();
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        user = await setupUser(provider, vault, 1_000 * UNIT);
+        await deposit(program, vault, user, 100 * UNIT);
+
+        [queuedWithdrawal] = anchor.web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("queued_withdrawal"),
+                vault.vaultState.toBuffer(),
+                user.keypair.publicKey.toBuffer(),
+            ],
+            program.programId
+        );
+
+        // 20 % of total_asset per hour, trip the breaker on the 2nd hit
+        await program.methods
+            .setRedemptionGate(2_000, new anchor.BN(3_600), 2)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+    });
+
+    it("lets withdrawals through up to the window cap", async () => {
+        await withdraw(program, vault, user, 15 * UNIT);
+
+        const state = await fetchState();
+        assert.strictEqual(state.gateWindowCap.toNumber(), 20 * UNIT);
+        assert.strictEqual(state.gateWindowOutflow.toNumber(), 15 * UNIT);
+    });
+
+    it("fails past the gate when no queue account is passed", async () => {
+        try {
+            await withdraw(program, vault, user, 10 * UNIT);
+            assert.fail("gate did not hold");
+        } catch (err: any) {
+            assert.include(err.toString(), "RedemptionGateExceeded");
+        }
+    });
+
+    it("queues withdrawals past the gate and auto-pauses on repeat hits", async () => {
+        await withdraw(program, vault, user, 10 * UNIT, { queuedWithdrawal });
+
+        let state = await fetchState();
+        assert.strictEqual(state.queuedRedeemShares.toNumber(), 10 * UNIT);
+        assert.strictEqual(state.totalShares.toNumber(), 85 * UNIT);
+        assert.isFalse(state.paused);
+        assert.strictEqual(await getTokenBalance(connection, user.shareAccount), 75 * UNIT);
+
+        await withdraw(program, vault, user, 10 * UNIT, { queuedWithdrawal });
+
+        state = await fetchState();
+        assert.strictEqual(state.queuedRedeemShares.toNumber(), 20 * UNIT);
+        assert.isTrue(state.paused);
+    });
+
+    it("pays queued withdrawals once the gate has room", async () => {
+        await program.methods
+            .setPause(false)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+        await program.methods
+            .setRedemptionGate(0, new anchor.BN(0), 0)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+
+        await processQueue();
+
+        const state = await fetchState();
+        assert.strictEqual(state.queuedRedeemShares.toNumber(), 0);
+        assert.strictEqual(state.totalShares.toNumber(), 65 * UNIT);
+        assert.strictEqual(state.totalAsset.toNumber(), 65 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, user.assetAccount), 935 * UNIT);
+        assert.isNull(await connection.getAccountInfo(queuedWithdrawal));
+    });
+
+    it("drains a queue larger than one window over several windows", async () => {
+        // 10 % per 5 s window: 6.5 of the 65 Asset A in the first one
+        await program.methods
+            .setRedemptionGate(1_000, new anchor.BN(5), 0)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+        await withdraw(program, vault, user, 20 * UNIT, { queuedWithdrawal });
+
+        await processQueue();
+        const queued = (await program.account.queuedWithdrawal.fetch(queuedWithdrawal)) as any;
+        assert.strictEqual(queued.shares.toNumber(), 13.5 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, user.assetAccount), 941.5 * UNIT);
+
+        try {
+            await processQueue();
+            assert.fail("paid past the window cap");
+        } catch (err: any) {
+            assert.include(err.toString(), "RedemptionGateExceeded");
+        }
+
+        let windows = 1;
+        while (await connection.getAccountInfo(queuedWithdrawal)) {
+            await sleep(5_500);
+            await processQueue();
+            windows++;
+        }
+        assert.isAtLeast(windows, 3);
+
+        const state = await fetchState();
+        assert.strictEqual(state.queuedRedeemShares.toNumber(), 0);
+        assert.strictEqual(state.totalShares.toNumber(), 45 * UNIT);
+        assert.strictEqual(state.totalAsset.toNumber(), 45 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, user.assetAccount), 955 * UNIT);
+    });
+});
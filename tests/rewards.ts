@@ -0,0 +1,179 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+    TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    createMint,
+    getOrCreateAssociatedTokenAccount,
+    mintTo,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    DECIMALS,
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    getTokenBalance,
+    sleep,
+} from "./helpers";
+
+describe("reward streams", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+    const payer = (provider.wallet as any).payer;
+
+    let vault: VaultFixture;
+    let user: UserFixture;
+    let rewardMint: anchor.web3.PublicKey;
+    let rewardPool: anchor.web3.PublicKey;
+    let rewardVault: anchor.web3.PublicKey;
+    let checkpoint: anchor.web3.PublicKey;
+    let userRewardAccount: anchor.web3.PublicKey;
+
+    const RATE = 1 * UNIT; // reward tokens per second
+
+    before(async () => {
+        // soulbound shares: checkpoints only move with deposits and withdrawals
+        vault = await setupVault(program, provider, { nonTransferable: true });
+        user = await setupUser(provider, vault, 1_000 * UNIT);
+
+        rewardMint = await createMint(connection, payer, provider.wallet.publicKey, null, DECIMALS);
+        [rewardPool] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("reward_pool"), vault.vaultState.toBuffer(), rewardMint.toBuffer()],
+            program.programId
+        );
+        [rewardVault] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("reward_vault"), rewardPool.toBuffer()],
+            program.programId
+        );
+        [checkpoint] = anchor.web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("reward_checkpoint"),
+                rewardPool.toBuffer(),
+                user.keypair.publicKey.toBuffer(),
+            ],
+            program.programId
+        );
+        userRewardAccount = (
+            await getOrCreateAssociatedTokenAccount(connection, payer, rewardMint, user.keypair.publicKey)
+        ).address;
+
+        await program.methods
+            .createRewardPool(new anchor.BN(RATE))
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool,
+                rewardVault,
+                vaultAuthority: vault.vaultAuthority,
+                rewardMint,
+                admin: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            } as any)
+            .rpc();
+
+        const funder = (
+            await getOrCreateAssociatedTokenAccount(connection, payer, rewardMint, provider.wallet.publicKey)
+        ).address;
+        await mintTo(connection, payer, rewardMint, funder, provider.wallet.publicKey, 1_000 * UNIT);
+        await program.methods
+            .fundRewardPool(new anchor.BN(1_000 * UNIT))
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool,
+                rewardVault,
+                funderTokenAccount: funder,
+                admin: provider.wallet.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            } as any)
+            .rpc();
+
+        // opt in with an empty balance
+        await program.methods
+            .updateRewardCheckpoint()
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool,
+                rewardCheckpoint: checkpoint,
+                userShareAccount: user.shareAccount,
+                user: user.keypair.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([user.keypair])
+            .rpc();
+    });
+
+    it("refuses deposits that leave the checkpoint out", async () => {
+        try {
+            await deposit(program, vault, user, 10 * UNIT);
+            assert.fail("deposit skipped the reward checkpoint");
+        } catch (err: any) {
+            assert.include(err.toString(), "InvalidRewardAccounts");
+        }
+    });
+
+    it("moves the checkpoint with deposits passed as remaining accounts", async () => {
+        await program.methods
+            .depositAssetA(new anchor.BN(100 * UNIT))
+            .accounts({
+                vaultState: vault.vaultState,
+                vaultAssetAccount: vault.vaultAssetAccount,
+                userAssetAccount: user.assetAccount,
+                receiver: user.keypair.publicKey,
+                receiverShareAccount: user.shareAccount,
+                shareMint: vault.shareMint,
+                vaultAuthority: vault.vaultAuthority,
+                position: null,
+                user: user.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .remainingAccounts([
+                { pubkey: rewardPool, isWritable: true, isSigner: false },
+                { pubkey: checkpoint, isWritable: true, isSigner: false },
+            ])
+            .signers([user.keypair])
+            .rpc();
+
+        const cp = (await program.account.rewardCheckpoint.fetch(checkpoint)) as any;
+        assert.strictEqual(cp.shares.toNumber(), 100 * UNIT);
+        assert.strictEqual(cp.pending.toNumber(), 0);
+    });
+
+    it("the sole holder earns the whole stream", async () => {
+        const before = Date.now();
+        await sleep(3_000);
+
+        await program.methods
+            .claimRewards()
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool,
+                rewardVault,
+                rewardCheckpoint: checkpoint,
+                userShareAccount: user.shareAccount,
+                userRewardAccount,
+                vaultAuthority: vault.vaultAuthority,
+                user: user.keypair.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            } as any)
+            .signers([user.keypair])
+            .rpc();
+
+        const claimed = await getTokenBalance(connection, userRewardAccount);
+        const elapsed = Math.ceil((Date.now() - before) / 1000) + 2; // clock slack
+        assert.isAbove(claimed, 0);
+        assert.isAtMost(claimed, elapsed * RATE);
+
+        const pool = (await program.account.rewardPool.fetch(rewardPool)) as any;
+        assert.strictEqual(pool.totalClaimed.toNumber(), claimed);
+    });
+});
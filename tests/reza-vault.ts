@@ -2,16 +2,25 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import {
     TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
     createMint,
     getOrCreateAssociatedTokenAccount,
     mintTo,
-    getAccount,
+    getTokenMetadata,
 } from "@solana/spl-token";
 import { assert } from "chai";
 import { RezaVault } from "../target/types/reza_vault";
+import {
+    SHARE_NAME,
+    SHARE_SYMBOL,
+    SHARE_URI,
+    prepareProtocol,
+    protocolConfigPda,
+} from "./helpers";
 
 const DECIMALS = 6;
 const UNIT = 10 ** DECIMALS; // smallest unit for convenience
+const VAULT_ID = new anchor.BN(0); // several vaults may share one asset mint
 
 
 /**
@@ -81,7 +90,11 @@ describe("reza-vault", () => {
         // Derive PDAs exactly as the program does
         [vaultState, stateBump] =
             anchor.web3.PublicKey.findProgramAddressSync(
-                [Buffer.from("vault_state"), assetMint.toBuffer()],
+                [
+                    Buffer.from("vault_state"),
+                    assetMint.toBuffer(),
+                    VAULT_ID.toArrayLike(Buffer, "le", 8),
+                ],
                 program.programId
             );
 
@@ -97,6 +110,7 @@ describe("reza-vault", () => {
                     Buffer.from("vault_asset"),
                     assetMint.toBuffer(),
                     vaultState.toBuffer(),
+                    VAULT_ID.toArrayLike(Buffer, "le", 8),
                 ],
                 program.programId
             );
@@ -122,8 +136,21 @@ describe("reza-vault", () => {
         );
 
         // ~~~~~~~~~ Initialise the vault ~~~~~~~~~
+        const { protocolConfig, vaultRegistry } = await prepareProtocol(
+            program,
+            provider,
+            assetMint
+        );
         await program.methods
-            .initializeVault(stateBump, authBump)
+            .initializeVault(
+                stateBump,
+                authBump,
+                VAULT_ID,
+                SHARE_NAME,
+                SHARE_SYMBOL,
+                SHARE_URI,
+                false
+            )
             .accounts({
                 vaultState,
                 vaultAuthority,
@@ -131,10 +158,13 @@ describe("reza-vault", () => {
                 admin: admin.publicKey,
                 assetMint,
                 shareMint: shareMintKP.publicKey,
+                protocolConfig,
+                vaultRegistry,
                 systemProgram: anchor.web3.SystemProgram.programId,
                 tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
                 rent: anchor.web3.SYSVAR_RENT_PUBKEY,
-            })
+            } as any)
             .signers([shareMintKP])
             .rpc();
 
@@ -146,7 +176,11 @@ describe("reza-vault", () => {
                 connection,
                 (admin as any).payer,
                 shareMint,
-                user.publicKey
+                user.publicKey,
+                false,
+                undefined,
+                undefined,
+                TOKEN_2022_PROGRAM_ID
             )
         ).address;
     });
@@ -158,7 +192,7 @@ describe("reza-vault", () => {
         (await program.account.vaultState.fetch(vaultState)) as any;
 
     const getTokenBalance = async (acc: anchor.web3.PublicKey) =>
-        Number((await getAccount(connection, acc)).amount);
+        Number((await connection.getTokenAccountBalance(acc)).value.amount);
 
     // ─────────────────────────────────────────────
     //  Tests
@@ -172,6 +206,102 @@ describe("reza-vault", () => {
         assert.ok(state.shareMint.equals(shareMint));
         assert.ok(state.vaultAssetAccount.equals(vaultAssetAccount));
         assert.ok(state.vaultAuthority.equals(vaultAuthority));
+        assert.strictEqual(state.vaultId.toNumber(), VAULT_ID.toNumber());
+    });
+
+    it("writes share-token metadata onto the mint", async () => {
+        const metadata = await getTokenMetadata(connection, shareMint);
+        assert.ok(metadata);
+        assert.strictEqual(metadata!.name, SHARE_NAME);
+        assert.strictEqual(metadata!.symbol, SHARE_SYMBOL);
+        assert.strictEqual(metadata!.uri, SHARE_URI);
+        assert.ok(metadata!.updateAuthority!.equals(vaultAuthority));
+    });
+
+    it("lets the admin update share-token metadata", async () => {
+        const uri = "https://example.com/reza-vault-share-v2-with-a-longer-uri.json";
+        await program.methods
+            .updateShareMetadata("Reza Vault Share v2", "rvSHARE2", uri)
+            .accounts({
+                vaultState,
+                shareMint,
+                vaultAuthority,
+                admin: admin.publicKey,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .rpc();
+
+        const metadata = await getTokenMetadata(connection, shareMint);
+        assert.strictEqual(metadata!.name, "Reza Vault Share v2");
+        assert.strictEqual(metadata!.symbol, "rvSHARE2");
+        assert.strictEqual(metadata!.uri, uri);
+    });
+
+    it("initialises a second vault for the same asset mint", async () => {
+        const vaultId = new anchor.BN(1);
+        const [secondState, secondStateBump] =
+            anchor.web3.PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("vault_state"),
+                    assetMint.toBuffer(),
+                    vaultId.toArrayLike(Buffer, "le", 8),
+                ],
+                program.programId
+            );
+        const [secondAuthority, secondAuthBump] =
+            anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault_authority"), secondState.toBuffer()],
+                program.programId
+            );
+        const [secondAssetAccount] =
+            anchor.web3.PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("vault_asset"),
+                    assetMint.toBuffer(),
+                    secondState.toBuffer(),
+                    vaultId.toArrayLike(Buffer, "le", 8),
+                ],
+                program.programId
+            );
+        const secondShareMint = anchor.web3.Keypair.generate();
+        const { protocolConfig, vaultRegistry } = await prepareProtocol(
+            program,
+            provider,
+            assetMint
+        );
+
+        await program.methods
+            .initializeVault(
+                secondStateBump,
+                secondAuthBump,
+                vaultId,
+                SHARE_NAME,
+                SHARE_SYMBOL,
+                SHARE_URI,
+                false
+            )
+            .accounts({
+                vaultState: secondState,
+                vaultAuthority: secondAuthority,
+                vaultAssetAccount: secondAssetAccount,
+                admin: admin.publicKey,
+                assetMint,
+                shareMint: secondShareMint.publicKey,
+                protocolConfig,
+                vaultRegistry,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            } as any)
+            .signers([secondShareMint])
+            .rpc();
+
+        const state = (await program.account.vaultState.fetch(secondState)) as any;
+        assert.strictEqual(state.vaultId.toNumber(), 1);
+        assert.ok(state.assetMint.equals(assetMint));
+        assert.ok(!secondState.equals(vaultState));
     });
 
     it("deposits 100 tokens and mints 100 shares", async () => {
@@ -190,7 +320,8 @@ describe("reza-vault", () => {
                 vaultState,
                 vaultAssetAccount,
                 userAssetAccount,
-                userShareAccount,
+                receiver: user.publicKey,
+                receiverShareAccount: userShareAccount,
                 shareMint,
                 vaultAuthority,
                 user: user.publicKey,
@@ -249,7 +380,8 @@ describe("reza-vault", () => {
                 vaultState,
                 vaultAssetAccount,
                 userAssetAccount,
-                userShareAccount,
+                receiver: user.publicKey,
+                receiverShareAccount: userShareAccount,
                 shareMint,
                 vaultAuthority,
                 user: user.publicKey,
@@ -278,11 +410,11 @@ describe("reza-vault", () => {
 
         // Send tx
         const txSig = await program.methods
-            .withdrawAssetA(shares)
+            .withdrawAssetA(shares, false)
             .accounts({
                 vaultState,
                 vaultAssetAccount,
-                userAssetAccount,
+                receiverAssetAccount: userAssetAccount,
                 userShareAccount,
                 shareMint,
                 vaultAuthority,
@@ -345,7 +477,8 @@ describe("reza-vault", () => {
                     vaultState,
                     vaultAssetAccount,
                     userAssetAccount,
-                    userShareAccount,
+                    receiver: user.publicKey,
+                receiverShareAccount: userShareAccount,
                     shareMint,
                     vaultAuthority,
                     user: user.publicKey,
@@ -374,7 +507,8 @@ describe("reza-vault", () => {
                     vaultState,
                     vaultAssetAccount,
                     userAssetAccount,
-                    userShareAccount,
+                    receiver: user.publicKey,
+                receiverShareAccount: userShareAccount,
                     shareMint,
                     vaultAuthority,
                     user: user.publicKey,
@@ -400,7 +534,8 @@ describe("reza-vault", () => {
                 vaultState,
                 vaultAssetAccount,
                 userAssetAccount,
-                userShareAccount,
+                receiver: user.publicKey,
+                receiverShareAccount: userShareAccount,
                 shareMint,
                 vaultAuthority,
                 user: user.publicKey,
@@ -0,0 +1,351 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+    TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    createMint,
+    getExtraAccountMetaAddress,
+    getOrCreateAssociatedTokenAccount,
+    mintTo,
+    transferCheckedWithTransferHook,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import { ShareHook } from "../target/types/share_hook";
+import {
+    DECIMALS,
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    getTokenBalance,
+    sleep,
+} from "./helpers";
+
+describe("share transfer hook", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+    const hook = anchor.workspace.ShareHook as Program<ShareHook>;
+    const payer = (provider.wallet as any).payer;
+
+    let vault: VaultFixture;
+    let alice: UserFixture;
+    let bob: UserFixture;
+    let carol: UserFixture;
+    let hookConfig: anchor.web3.PublicKey;
+    let rewardMint: anchor.web3.PublicKey;
+    let rewardPool: anchor.web3.PublicKey;
+    let rewardVault: anchor.web3.PublicKey;
+
+    const checkpointPda = (user: UserFixture) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("reward_checkpoint"),
+                rewardPool.toBuffer(),
+                user.keypair.publicKey.toBuffer(),
+            ],
+            program.programId
+        )[0];
+
+    // (pool, checkpoint) pair every deposit into a vault with a reward pool passes
+    const checkpointAccounts = (user: UserFixture) => [
+        { pubkey: rewardPool, isWritable: true, isSigner: false },
+        { pubkey: checkpointPda(user), isWritable: true, isSigner: false },
+    ];
+
+    const syncCheckpoint = (user: UserFixture) =>
+        program.methods
+            .updateRewardCheckpoint()
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool,
+                rewardCheckpoint: checkpointPda(user),
+                userShareAccount: user.shareAccount,
+                user: user.keypair.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .signers([user.keypair])
+            .rpc();
+
+    const positionPda = (user: UserFixture) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("position"), vault.vaultState.toBuffer(), user.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const userPositionPda = (user: UserFixture) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("user_position"), vault.vaultState.toBuffer(), user.keypair.publicKey.toBuffer()],
+            program.programId
+        )[0];
+
+    const allow = (user: UserFixture) =>
+        hook.methods
+            .allowWallet(user.keypair.publicKey)
+            .accounts({
+                hookConfig,
+                vaultState: vault.vaultState,
+                admin: provider.wallet.publicKey,
+            } as any)
+            .rpc();
+
+    const transfer = (from: UserFixture, to: UserFixture, amount: number) =>
+        transferCheckedWithTransferHook(
+            connection,
+            payer,
+            from.shareAccount,
+            vault.shareMint,
+            to.shareAccount,
+            from.keypair,
+            BigInt(amount),
+            DECIMALS,
+            [],
+            undefined,
+            TOKEN_2022_PROGRAM_ID
+        );
+
+    const errorText = (err: any) => err.toString() + (err.logs ?? []).join("\n");
+
+    before(async () => {
+        vault = await setupVault(program, provider, { transferHookProgram: hook.programId });
+        alice = await setupUser(provider, vault, 1_000 * UNIT);
+        bob = await setupUser(provider, vault, 0);
+        carol = await setupUser(provider, vault, 1_000 * UNIT);
+
+        [hookConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("hook_config"), vault.shareMint.toBuffer()],
+            hook.programId
+        );
+        await hook.methods
+            .initializeHook()
+            .accounts({
+                hookConfig,
+                extraAccountMetaList: getExtraAccountMetaAddress(vault.shareMint, hook.programId),
+                vaultState: vault.vaultState,
+                mint: vault.shareMint,
+                admin: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .rpc();
+
+        // a reward pool whose checkpoints the hook keeps in sync
+        rewardMint = await createMint(connection, payer, provider.wallet.publicKey, null, DECIMALS);
+        [rewardPool] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("reward_pool"), vault.vaultState.toBuffer(), rewardMint.toBuffer()],
+            program.programId
+        );
+        [rewardVault] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("reward_vault"), rewardPool.toBuffer()],
+            program.programId
+        );
+        await program.methods
+            .createRewardPool(new anchor.BN(0))
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool,
+                rewardVault,
+                vaultAuthority: vault.vaultAuthority,
+                rewardMint,
+                admin: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            } as any)
+            .rpc();
+        await hook.methods
+            .addRewardPool()
+            .accounts({
+                hookConfig,
+                extraAccountMetaList: getExtraAccountMetaAddress(vault.shareMint, hook.programId),
+                vaultState: vault.vaultState,
+                rewardPool,
+                admin: provider.wallet.publicKey,
+            } as any)
+            .rpc();
+
+        for (const user of [alice, bob]) {
+            await syncCheckpoint(user);
+        }
+
+        await deposit(program, vault, alice, 100 * UNIT, {}, checkpointAccounts(alice));
+    });
+
+    it("refuses any transfer-hook program other than share-hook", async () => {
+        try {
+            await setupVault(program, provider, {
+                transferHookProgram: anchor.workspace.MockAmm.programId,
+            });
+            assert.fail("initialised a vault with a foreign transfer hook");
+        } catch (err: any) {
+            assert.include(errorText(err), "InvalidTransferHookProgram");
+        }
+    });
+
+    it("blocks transfers between wallets that are not allowlisted", async () => {
+        try {
+            await transfer(alice, bob, 10 * UNIT);
+            assert.fail("transfer went through without an allowlist entry");
+        } catch (err: any) {
+            assert.include(errorText(err), "SenderNotAllowed");
+        }
+
+        await allow(alice);
+        try {
+            await transfer(alice, bob, 10 * UNIT);
+            assert.fail("transfer to a non-allowlisted receiver");
+        } catch (err: any) {
+            assert.include(errorText(err), "ReceiverNotAllowed");
+        }
+    });
+
+    it("moves shares and both reward checkpoints between allowlisted wallets", async () => {
+        await allow(bob);
+        await transfer(alice, bob, 30 * UNIT);
+
+        assert.strictEqual(await getTokenBalance(connection, alice.shareAccount), 70 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, bob.shareAccount), 30 * UNIT);
+
+        const aliceCp = (await program.account.rewardCheckpoint.fetch(checkpointPda(alice))) as any;
+        const bobCp = (await program.account.rewardCheckpoint.fetch(checkpointPda(bob))) as any;
+        assert.strictEqual(aliceCp.shares.toNumber(), 70 * UNIT);
+        assert.strictEqual(bobCp.shares.toNumber(), 30 * UNIT);
+    });
+
+    it("refuses to move shares that are still locked", async () => {
+        await program.methods
+            .setLockup(new anchor.BN(3_600), 0)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+        await allow(carol);
+        // carol never opted in, her checkpoint PDA is passed empty
+        await deposit(
+            program,
+            vault,
+            carol,
+            50 * UNIT,
+            { position: positionPda(carol) },
+            checkpointAccounts(carol)
+        );
+
+        try {
+            await transfer(carol, bob, 10 * UNIT);
+            assert.fail("locked shares were transferred");
+        } catch (err: any) {
+            assert.include(errorText(err), "SharesLocked");
+        }
+    });
+
+    it("pays shares moved between two checkpointed wallets only once", async () => {
+        const funder = (
+            await getOrCreateAssociatedTokenAccount(connection, payer, rewardMint, provider.wallet.publicKey)
+        ).address;
+        await mintTo(connection, payer, rewardMint, funder, provider.wallet.publicKey, 1_000 * UNIT);
+        await program.methods
+            .fundRewardPool(new anchor.BN(1_000 * UNIT))
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool,
+                rewardVault,
+                funderTokenAccount: funder,
+                admin: provider.wallet.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            } as any)
+            .rpc();
+        await program.methods
+            .setRewardRate(new anchor.BN(UNIT))
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool,
+                admin: provider.wallet.publicKey,
+            } as any)
+            .rpc();
+
+        // the same 30 shares go back and forth while the stream runs
+        await sleep(1_500);
+        await transfer(bob, alice, 30 * UNIT);
+        await sleep(1_500);
+        await transfer(alice, bob, 30 * UNIT);
+        await sleep(1_500);
+        await syncCheckpoint(alice);
+        await syncCheckpoint(bob);
+
+        const pool = (await program.account.rewardPool.fetch(rewardPool)) as any;
+        const aliceCp = (await program.account.rewardCheckpoint.fetch(checkpointPda(alice))) as any;
+        const bobCp = (await program.account.rewardCheckpoint.fetch(checkpointPda(bob))) as any;
+        const owed = aliceCp.pending.toNumber() + bobCp.pending.toNumber();
+        assert.isAbove(owed, 0);
+        assert.isAtMost(owed + pool.unclaimable.toNumber(), pool.totalAccrued.toNumber());
+        // carol's un-opted-in shares neither earn nor dilute the stream
+        assert.strictEqual(pool.stakedShares.toNumber(), 100 * UNIT);
+        assert.strictEqual(
+            pool.stakedShares.toNumber(),
+            aliceCp.shares.toNumber() + bobCp.shares.toNumber()
+        );
+    });
+
+    it("moves the cost basis of transferred shares with them", async () => {
+        for (const [user, amount] of [
+            [alice, 20 * UNIT],
+            [carol, 10 * UNIT],
+        ] as [UserFixture, number][]) {
+            await deposit(
+                program,
+                vault,
+                user,
+                amount,
+                { position: positionPda(user), userPosition: userPositionPda(user) },
+                checkpointAccounts(user)
+            );
+        }
+        const basis = async (user: UserFixture) => {
+            const position = (await program.account.userPosition.fetch(userPositionPda(user))) as any;
+            return [position.shares.toNumber(), position.costBasis.toNumber()];
+        };
+        const [aliceShares, aliceCost] = await basis(alice);
+        const [carolShares, carolCost] = await basis(carol);
+
+        await transfer(alice, carol, 10 * UNIT);
+
+        // the moved shares take alice's average cost along
+        const moved = Math.floor((aliceCost * 10 * UNIT) / aliceShares);
+        assert.deepEqual(await basis(alice), [aliceShares - 10 * UNIT, aliceCost - moved]);
+        assert.deepEqual(await basis(carol), [carolShares + 10 * UNIT, carolCost + moved]);
+    });
+
+    it("rejects transfers until the hook tracks every reward pool", async () => {
+        const otherMint = await createMint(connection, payer, provider.wallet.publicKey, null, DECIMALS);
+        const [otherPool] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("reward_pool"), vault.vaultState.toBuffer(), otherMint.toBuffer()],
+            program.programId
+        );
+        const [otherVault] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("reward_vault"), otherPool.toBuffer()],
+            program.programId
+        );
+        await program.methods
+            .createRewardPool(new anchor.BN(0))
+            .accounts({
+                vaultState: vault.vaultState,
+                rewardPool: otherPool,
+                rewardVault: otherVault,
+                vaultAuthority: vault.vaultAuthority,
+                rewardMint: otherMint,
+                admin: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            } as any)
+            .rpc();
+
+        try {
+            await transfer(alice, bob, 10 * UNIT);
+            assert.fail("transfer skipped a reward pool");
+        } catch (err: any) {
+            assert.include(errorText(err), "InvalidRewardAccounts");
+        }
+    });
+});
@@ -0,0 +1,142 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    protocolConfigPda,
+} from "./helpers";
+
+describe("share price feed", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let alice: UserFixture;
+    let borrower: UserFixture;
+
+    const feedPda = () =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("share_price_feed"), vault.vaultState.toBuffer()],
+            program.programId
+        )[0];
+
+    // decode by offset, the way an integrator without the IDL would
+    const readFeed = async () => {
+        const data = (await connection.getAccountInfo(feedPda()))!.data;
+        return {
+            vault: new anchor.web3.PublicKey(data.subarray(8, 40)),
+            price: Number(data.readBigUInt64LE(72)),
+            expo: data.readInt32LE(80),
+            lastPriceMove: Number(data.readBigUInt64LE(84)),
+            totalAsset: Number(data.readBigUInt64LE(92)),
+            totalShares: Number(data.readBigUInt64LE(100)),
+            lastUpdateSlot: Number(data.readBigUInt64LE(108)),
+            maxStalenessSlots: Number(data.readBigUInt64LE(124)),
+        };
+    };
+
+    before(async () => {
+        vault = await setupVault(program, provider);
+        alice = await setupUser(provider, vault, 1_000 * UNIT);
+        borrower = await setupUser(provider, vault, 10 * UNIT);
+
+        await program.methods
+            .createSharePriceFeed(new anchor.BN(150))
+            .accounts({
+                vaultState: vault.vaultState,
+                sharePriceFeed: feedPda(),
+                admin: provider.wallet.publicKey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            } as any)
+            .rpc();
+        await program.methods
+            .setFlashLoanFee(1_000)
+            .accounts({ vaultState: vault.vaultState, admin: provider.wallet.publicKey } as any)
+            .rpc();
+    });
+
+    it("publishes the price with a fixed byte layout", async () => {
+        await deposit(program, vault, alice, 100 * UNIT, { sharePriceFeed: feedPda() });
+
+        const feed = await readFeed();
+        assert.isTrue(feed.vault.equals(vault.vaultState));
+        assert.strictEqual(feed.expo, -9);
+        assert.strictEqual(feed.price, 1_000_000_000);
+        assert.strictEqual(feed.totalAsset, 100 * UNIT);
+        assert.strictEqual(feed.totalShares, 100 * UNIT);
+        assert.strictEqual(feed.maxStalenessSlots, 150);
+        assert.isAbove(feed.lastUpdateSlot, 0);
+    });
+
+    it("follows price moves and reports the last move", async () => {
+        const accounts = {
+            vaultState: vault.vaultState,
+            vaultAssetAccount: vault.vaultAssetAccount,
+            borrowerAssetAccount: borrower.assetAccount,
+            protocolConfig: protocolConfigPda(program),
+            borrower: borrower.keypair.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        };
+        const borrowIx = await program.methods
+            .flashBorrow(new anchor.BN(50 * UNIT))
+            .accounts({ ...accounts, instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY } as any)
+            .instruction();
+        const repayIx = await program.methods
+            .flashRepay()
+            .accounts({ ...accounts, sharePriceFeed: feedPda() } as any)
+            .instruction();
+        await provider.sendAndConfirm(new anchor.web3.Transaction().add(borrowIx, repayIx), [
+            borrower.keypair,
+        ]);
+
+        const feed = await readFeed();
+        assert.strictEqual(feed.price, 1_050_000_000); // 105 assets / 100 shares
+        assert.strictEqual(feed.lastPriceMove, 50_000_000);
+    });
+
+    it("requires the feed on every state change once it exists", async () => {
+        try {
+            await deposit(program, vault, alice, 1 * UNIT);
+            assert.fail("deposited without refreshing the feed");
+        } catch (err: any) {
+            assert.include(err.toString(), "SharePriceFeedRequired");
+        }
+    });
+
+    it("rejects a feed that never goes stale", async () => {
+        try {
+            await program.methods
+                .setFeedStaleness(new anchor.BN(0))
+                .accounts({
+                    vaultState: vault.vaultState,
+                    sharePriceFeed: feedPda(),
+                    admin: provider.wallet.publicKey,
+                } as any)
+                .rpc();
+            assert.fail("staleness check disabled");
+        } catch (err: any) {
+            assert.include(err.toString(), "InvalidFeedStaleness");
+        }
+    });
+
+    it("lets anyone refresh the feed", async () => {
+        const before = await readFeed();
+        await program.methods
+            .refreshSharePriceFeed()
+            .accounts({ vaultState: vault.vaultState, sharePriceFeed: feedPda() } as any)
+            .rpc();
+
+        const feed = await readFeed();
+        assert.isAbove(feed.lastUpdateSlot, before.lastUpdateSlot);
+        assert.strictEqual(feed.lastPriceMove, 0);
+    });
+});
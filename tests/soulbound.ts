@@ -0,0 +1,75 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+    TOKEN_2022_PROGRAM_ID,
+    ExtensionType,
+    getExtensionTypes,
+    getMint,
+    transferChecked,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { RezaVault } from "../target/types/reza_vault";
+import {
+    DECIMALS,
+    UNIT,
+    VaultFixture,
+    UserFixture,
+    setupVault,
+    setupUser,
+    deposit,
+    withdraw,
+    getTokenBalance,
+} from "./helpers";
+
+describe("soulbound shares", () => {
+    anchor.setProvider(anchor.AnchorProvider.env());
+    const provider = anchor.getProvider<anchor.AnchorProvider>();
+    const connection = provider.connection;
+    const program = anchor.workspace.RezaVault as Program<RezaVault>;
+
+    let vault: VaultFixture;
+    let alice: UserFixture;
+    let bob: UserFixture;
+
+    before(async () => {
+        vault = await setupVault(program, provider, { nonTransferable: true });
+        alice = await setupUser(provider, vault, 1_000 * UNIT);
+        bob = await setupUser(provider, vault, 0);
+    });
+
+    it("creates the share mint with the non-transferable extension", async () => {
+        const mint = await getMint(connection, vault.shareMint, undefined, TOKEN_2022_PROGRAM_ID);
+        assert.include(getExtensionTypes(mint.tlvData), ExtensionType.NonTransferable);
+
+        const state = (await program.account.vaultState.fetch(vault.vaultState)) as any;
+        assert.isTrue(state.nonTransferable);
+    });
+
+    it("still mints on deposit and burns on withdraw", async () => {
+        await deposit(program, vault, alice, 100 * UNIT);
+        await withdraw(program, vault, alice, 40 * UNIT);
+        assert.strictEqual(await getTokenBalance(connection, alice.shareAccount), 60 * UNIT);
+    });
+
+    it("refuses wallet-to-wallet share transfers", async () => {
+        try {
+            await transferChecked(
+                connection,
+                (provider.wallet as any).payer,
+                alice.shareAccount,
+                vault.shareMint,
+                bob.shareAccount,
+                alice.keypair,
+                10 * UNIT,
+                DECIMALS,
+                [],
+                undefined,
+                TOKEN_2022_PROGRAM_ID
+            );
+            assert.fail("soulbound shares were transferred");
+        } catch (err: any) {
+            assert.include(err.toString(), "0x25"); // NonTransferable
+        }
+        assert.strictEqual(await getTokenBalance(connection, bob.shareAccount), 0);
+    });
+});